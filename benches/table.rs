@@ -0,0 +1,103 @@
+//! Compares the station table against the `HashMap<&[u8], Statistics>` it
+//! replaced, for the fold over records and the reduce over per-thread maps.
+
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use low_effort_1brc::{StationTable, Statistics};
+
+const RECORDS: usize = 1_000_000;
+
+/// `stations` names between 3 and 40 bytes long, and a stream of records
+/// cycling through them.
+fn records(stations: usize) -> Vec<(Vec<u8>, i16)> {
+    let names: Vec<Vec<u8>> = (0..stations)
+        .map(|i| format!("{}-{i}", "Station".repeat(i % 5)).into_bytes())
+        .collect();
+    (0..RECORDS)
+        .map(|i| {
+            let name = &names[(i * 7919) % stations];
+            (name.clone(), (i % 1999) as i16 - 999)
+        })
+        .collect()
+}
+
+fn fold_hash_map(records: &[(Vec<u8>, i16)]) -> HashMap<&[u8], Statistics> {
+    let mut map = HashMap::<&[u8], Statistics>::new();
+    for (station, temperature) in records {
+        if let Some(stored_stats) = map.get_mut(station.as_slice()) {
+            stored_stats.add_measurement(*temperature);
+        } else {
+            map.insert(station, Statistics::new(*temperature));
+        }
+    }
+    map
+}
+
+fn fold_table(records: &[(Vec<u8>, i16)]) -> StationTable {
+    let mut table = StationTable::new();
+    for (station, temperature) in records {
+        table.add(station, *temperature);
+    }
+    table
+}
+
+fn fold(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fold");
+    group.throughput(Throughput::Elements(RECORDS as u64));
+    for stations in [413, 10_000] {
+        let records = records(stations);
+        group.bench_with_input(
+            BenchmarkId::new("HashMap", stations),
+            &records,
+            |b, records| b.iter(|| fold_hash_map(records)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("StationTable", stations),
+            &records,
+            |b, records| b.iter(|| fold_table(records)),
+        );
+    }
+    group.finish();
+}
+
+fn reduce(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reduce");
+    for stations in [413, 10_000] {
+        let records = records(stations);
+        let maps: Vec<_> = records.chunks(RECORDS / 16).map(fold_hash_map).collect();
+        let tables: Vec<_> = records.chunks(RECORDS / 16).map(fold_table).collect();
+        group.bench_with_input(BenchmarkId::new("HashMap", stations), &maps, |b, maps| {
+            b.iter(|| {
+                let mut left = HashMap::<&[u8], Statistics>::new();
+                for map in maps {
+                    for (station, stats) in map {
+                        if let Some(stored_stats) = left.get_mut(station) {
+                            stored_stats.merge(stats);
+                        } else {
+                            left.insert(station, stats.clone());
+                        }
+                    }
+                }
+                left
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("StationTable", stations),
+            &tables,
+            |b, tables| {
+                b.iter(|| {
+                    let mut left = StationTable::new();
+                    for table in tables {
+                        left.merge(table);
+                    }
+                    left
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, fold, reduce);
+criterion_main!(benches);
@@ -0,0 +1,598 @@
+use std::fmt;
+
+use crate::{
+    statistics::{mean, variance},
+    Stat, Statistics, MAX_TENTHS,
+};
+
+/// Measurements a [`Histogram`] keeps as they are before counting them,
+/// as many bytes as 256 counts.
+const MAX_SAMPLES: usize = 512;
+
+/// Statistic of the measurements of a single station, in tenths of a degree.
+///
+/// Accumulators of different parts of the input are merged in any order, so
+/// `merge` has to be associative and commutative. Tuples of accumulators are
+/// accumulators computing all of their statistics.
+pub trait Accumulator: Clone + Send {
+    /// Starts from the first measurement of a station.
+    fn init(measurement: i16) -> Self;
+
+    fn add(&mut self, measurement: i16);
+
+    /// Adds the measurements of `other`.
+    fn merge(&mut self, other: &Self);
+
+    /// Fills in the statistics known to this accumulator.
+    fn finalize<'a>(&'a self, summary: &mut Summary<'a>);
+}
+
+/// Final statistics of a station, `None` for those nobody computed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Summary<'a> {
+    pub count: Option<usize>,
+    pub min: Option<i16>,
+    pub max: Option<i16>,
+    /// Sum in tenths of a degree.
+    pub sum: Option<i64>,
+    /// Mean in tenths of a degree, see [`Statistics::average`].
+    pub mean: Option<i64>,
+    /// Population variance in squared degrees.
+    pub variance: Option<f64>,
+    pub histogram: Option<&'a Histogram>,
+}
+
+impl Summary<'_> {
+    /// Summary of `stats`.
+    pub fn of<A: Accumulator>(stats: &A) -> Summary<'_> {
+        let mut summary = Summary::default();
+        stats.finalize(&mut summary);
+        summary
+    }
+
+    /// Population standard deviation in degrees.
+    pub fn stddev(&self) -> Option<f64> {
+        self.variance.map(f64::sqrt)
+    }
+
+    /// See [`Histogram::percentile`].
+    pub fn percentile(&self, percentile: Percentile) -> Option<i16> {
+        self.histogram?.percentile(percentile)
+    }
+}
+
+/// Number of measurements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Count(pub usize);
+
+impl Accumulator for Count {
+    #[inline]
+    fn init(_: i16) -> Self {
+        Count(1)
+    }
+
+    #[inline]
+    fn add(&mut self, _: i16) {
+        self.0 += 1;
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        self.0 += other.0;
+    }
+
+    fn finalize<'a>(&'a self, summary: &mut Summary<'a>) {
+        summary.count = Some(self.0);
+    }
+}
+
+/// Lowest measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Min(pub i16);
+
+impl Accumulator for Min {
+    #[inline]
+    fn init(measurement: i16) -> Self {
+        Min(measurement)
+    }
+
+    #[inline]
+    fn add(&mut self, measurement: i16) {
+        self.0 = measurement.min(self.0);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        self.add(other.0);
+    }
+
+    fn finalize<'a>(&'a self, summary: &mut Summary<'a>) {
+        summary.min = Some(self.0);
+    }
+}
+
+/// Highest measurement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Max(pub i16);
+
+impl Accumulator for Max {
+    #[inline]
+    fn init(measurement: i16) -> Self {
+        Max(measurement)
+    }
+
+    #[inline]
+    fn add(&mut self, measurement: i16) {
+        self.0 = measurement.max(self.0);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        self.add(other.0);
+    }
+
+    fn finalize<'a>(&'a self, summary: &mut Summary<'a>) {
+        summary.max = Some(self.0);
+    }
+}
+
+/// Sum of the measurements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sum(pub i64);
+
+impl Accumulator for Sum {
+    #[inline]
+    fn init(measurement: i16) -> Self {
+        Sum(measurement.into())
+    }
+
+    #[inline]
+    fn add(&mut self, measurement: i16) {
+        self.0 += measurement as i64;
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        self.0 += other.0;
+    }
+
+    fn finalize<'a>(&'a self, summary: &mut Summary<'a>) {
+        summary.sum = Some(self.0);
+    }
+}
+
+/// Mean of the measurements, along with their sum and count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mean {
+    sum: Sum,
+    count: Count,
+}
+
+impl Accumulator for Mean {
+    #[inline]
+    fn init(measurement: i16) -> Self {
+        Mean {
+            sum: Sum::init(measurement),
+            count: Count::init(measurement),
+        }
+    }
+
+    #[inline]
+    fn add(&mut self, measurement: i16) {
+        self.sum.add(measurement);
+        self.count.add(measurement);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        self.sum.merge(&other.sum);
+        self.count.merge(&other.count);
+    }
+
+    fn finalize<'a>(&'a self, summary: &mut Summary<'a>) {
+        self.sum.finalize(summary);
+        self.count.finalize(summary);
+        summary.mean = Some(mean(self.sum.0, self.count.0));
+    }
+}
+
+/// Variance and standard deviation of the measurements from exact integer
+/// sums, along with their mean, sum and count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StdDev {
+    mean: Mean,
+    sum_squares: i64,
+}
+
+impl Accumulator for StdDev {
+    #[inline]
+    fn init(measurement: i16) -> Self {
+        StdDev {
+            mean: Mean::init(measurement),
+            sum_squares: measurement as i64 * measurement as i64,
+        }
+    }
+
+    #[inline]
+    fn add(&mut self, measurement: i16) {
+        self.mean.add(measurement);
+        self.sum_squares += measurement as i64 * measurement as i64;
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        self.mean.merge(&other.mean);
+        self.sum_squares += other.sum_squares;
+    }
+
+    fn finalize<'a>(&'a self, summary: &mut Summary<'a>) {
+        self.mean.finalize(summary);
+        let Mean { sum, count } = self.mean;
+        summary.variance = Some(variance(sum.0, self.sum_squares, count.0));
+    }
+}
+
+/// Count of every measurement, for exact percentiles since measurements are
+/// whole tenths.
+///
+/// The first 512 measurements are kept as they are, which is all
+/// of them for most stations of a part of the input when there are many
+/// stations and threads. Later ones are counted in buckets covering only the
+/// range of measurements seen. Buckets saturate at `u32::MAX`, past 4 billion
+/// measurements of the same tenth by a single station, rather than wrap.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    samples: Vec<i16>,
+    /// Measurement counted by `counts[0]`.
+    low: i16,
+    counts: Vec<u32>,
+}
+
+impl Histogram {
+    /// Adds `count` measurements of `measurement` to the buckets.
+    #[inline]
+    fn count(&mut self, measurement: i16, count: u32) {
+        if self.counts.is_empty() {
+            self.low = measurement;
+        }
+        if !(self.low..self.low + self.counts.len() as i16).contains(&measurement) {
+            self.widen(measurement);
+        }
+        let bucket = &mut self.counts[(measurement - self.low) as usize];
+        *bucket = bucket.saturating_add(count);
+    }
+
+    /// Extends the buckets to cover `measurement`.
+    #[cold]
+    fn widen(&mut self, measurement: i16) {
+        if measurement < self.low {
+            let extra = (self.low - measurement) as usize;
+            self.counts.splice(0..0, std::iter::repeat_n(0, extra));
+            self.low = measurement;
+        } else {
+            self.counts.resize((measurement - self.low) as usize + 1, 0);
+        }
+    }
+
+    /// Every measurement counted in buckets, from the lowest one.
+    fn counted(&self) -> (i16, Vec<u32>) {
+        let mut histogram = Histogram {
+            samples: Vec::new(),
+            ..self.clone()
+        };
+        for sample in &self.samples {
+            histogram.count(*sample, 1);
+        }
+        (histogram.low, histogram.counts)
+    }
+
+    /// Smallest measurement that `percentile` of the measurements are lower
+    /// than or equal to, by the nearest rank method.
+    pub fn percentile(&self, percentile: Percentile) -> Option<i16> {
+        let (low, counts) = self.counted();
+        let count: u64 = counts.iter().map(|count| *count as u64).sum();
+        let rank = (percentile.0 as u64 * count)
+            .div_ceil(Percentile::MAX.0 as u64)
+            .max(1);
+        let mut seen = 0;
+        counts.iter().enumerate().find_map(|(bucket, count)| {
+            seen += *count as u64;
+            (seen >= rank).then_some(low + bucket as i16)
+        })
+    }
+}
+
+/// Percentile in thousandths of a percent, so that ranks are exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Percentile(u32);
+
+impl Percentile {
+    pub const MAX: Percentile = Percentile(100_000);
+
+    /// The percentile of `thousandths` of a percent, up to 100%.
+    pub const fn new(thousandths: u32) -> Option<Self> {
+        match thousandths <= Percentile::MAX.0 {
+            true => Some(Percentile(thousandths)),
+            false => None,
+        }
+    }
+
+    pub const fn thousandths(self) -> u32 {
+        self.0
+    }
+}
+
+/// Writes the percent without trailing zeros, like `50` or `99.9`.
+impl fmt::Display for Percentile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0 / 1000)?;
+        match self.0 % 1000 {
+            0 => Ok(()),
+            fraction => write!(f, ".{}", format!("{fraction:03}").trim_end_matches('0')),
+        }
+    }
+}
+
+/// Histograms are equal when they count the same measurements, however they
+/// store them.
+impl PartialEq for Histogram {
+    fn eq(&self, other: &Self) -> bool {
+        self.counted() == other.counted()
+    }
+}
+
+impl Eq for Histogram {}
+
+impl Accumulator for Histogram {
+    fn init(measurement: i16) -> Self {
+        let mut histogram = Histogram {
+            samples: Vec::new(),
+            low: 0,
+            counts: Vec::new(),
+        };
+        histogram.add(measurement);
+        histogram
+    }
+
+    #[inline]
+    fn add(&mut self, measurement: i16) {
+        // Only unchecked garbage falls outside the range.
+        let measurement = measurement.clamp(-MAX_TENTHS, MAX_TENTHS);
+        // Below `low` wraps around past the end.
+        let index = (measurement as isize - self.low as isize) as usize;
+        if let Some(count) = self.counts.get_mut(index) {
+            *count = count.saturating_add(1);
+        } else if self.counts.is_empty() && self.samples.len() < MAX_SAMPLES {
+            self.samples.push(measurement);
+        } else {
+            self.count(measurement, 1);
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for sample in &other.samples {
+            self.add(*sample);
+        }
+        for (bucket, count) in other.counts.iter().enumerate() {
+            if *count > 0 {
+                self.count(other.low + bucket as i16, *count);
+            }
+        }
+    }
+
+    fn finalize<'a>(&'a self, summary: &mut Summary<'a>) {
+        summary.histogram = Some(self);
+    }
+}
+
+impl Accumulator for Statistics {
+    #[inline]
+    fn init(measurement: i16) -> Self {
+        Statistics::new(measurement)
+    }
+
+    #[inline]
+    fn add(&mut self, measurement: i16) {
+        self.add_measurement(measurement);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        Statistics::merge(self, other);
+    }
+
+    fn finalize<'a>(&'a self, summary: &mut Summary<'a>) {
+        summary.count = Some(self.count());
+        summary.min = Some(self.min());
+        summary.max = Some(self.max());
+        summary.sum = Some(self.sum());
+        summary.mean = Some(self.average());
+        summary.variance = Some(self.variance());
+    }
+}
+
+/// Computes nothing, for the unused parts of composed accumulators.
+impl Accumulator for () {
+    #[inline]
+    fn init(_: i16) -> Self {}
+
+    #[inline]
+    fn add(&mut self, _: i16) {}
+
+    #[inline]
+    fn merge(&mut self, _: &Self) {}
+
+    fn finalize<'a>(&'a self, _: &mut Summary<'a>) {}
+}
+
+macro_rules! tuple_accumulator {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: Accumulator),+> Accumulator for ($($name,)+) {
+            #[inline]
+            fn init(measurement: i16) -> Self {
+                ($($name::init(measurement),)+)
+            }
+
+            #[inline]
+            fn add(&mut self, measurement: i16) {
+                $(self.$index.add(measurement);)+
+            }
+
+            #[inline]
+            fn merge(&mut self, other: &Self) {
+                $(self.$index.merge(&other.$index);)+
+            }
+
+            fn finalize<'a>(&'a self, summary: &mut Summary<'a>) {
+                $(self.$index.finalize(summary);)+
+            }
+        }
+    };
+}
+
+tuple_accumulator!(A 0, B 1);
+tuple_accumulator!(A 0, B 1, C 2);
+tuple_accumulator!(A 0, B 1, C 2, D 3);
+
+/// Code generic over the accumulator, run by [`with_accumulator`].
+pub trait WithAccumulator {
+    type Output;
+
+    fn call<A: Accumulator>(self) -> Self::Output;
+}
+
+/// Calls `f` with the cheapest composition of accumulators computing `stats`,
+/// and a [`Histogram`] when `percentiles` are needed.
+///
+/// The min and max come together, the other statistics with the count, mean
+/// or variance they build on.
+pub fn with_accumulator<F: WithAccumulator>(stats: &[Stat], percentiles: bool, f: F) -> F::Output {
+    let requested = |wanted: &[Stat]| stats.iter().any(|stat| wanted.contains(stat));
+    if requested(&[Stat::Min, Stat::Max]) {
+        with_moments::<(Min, Max), F>(stats, percentiles, f)
+    } else {
+        with_moments::<(), F>(stats, percentiles, f)
+    }
+}
+
+fn with_moments<E: Accumulator, F: WithAccumulator>(
+    stats: &[Stat],
+    percentiles: bool,
+    f: F,
+) -> F::Output {
+    let requested = |wanted: &[Stat]| stats.iter().any(|stat| wanted.contains(stat));
+    if requested(&[Stat::Stddev, Stat::Variance]) {
+        with_histogram::<(E, StdDev), F>(percentiles, f)
+    } else if requested(&[Stat::Mean, Stat::Sum]) {
+        with_histogram::<(E, Mean), F>(percentiles, f)
+    } else {
+        with_histogram::<(E, Count), F>(percentiles, f)
+    }
+}
+
+fn with_histogram<A: Accumulator, F: WithAccumulator>(percentiles: bool, f: F) -> F::Output {
+    match percentiles {
+        true => f.call::<(A, Histogram)>(),
+        false => f.call::<A>(),
+    }
+}
+
+#[cfg(test)]
+fn percent(percent: f64) -> Percentile {
+    Percentile::new((percent * 1000.0).round() as u32).unwrap()
+}
+
+#[cfg(test)]
+fn accumulate<A: Accumulator>(measurements: &[i16]) -> A {
+    let mut stats = A::init(measurements[0]);
+    measurements[1..].iter().for_each(|m| stats.add(*m));
+    stats
+}
+
+#[test]
+fn compositions_match_statistics() {
+    let measurements: Vec<i16> = (0..1000).map(|i| (i * 37 % 1999 - 999) as i16).collect();
+    let expected = accumulate::<Statistics>(&measurements);
+    let mut stats = accumulate::<((Min, Max), StdDev, Sum)>(&measurements[..300]);
+    stats.merge(&accumulate(&measurements[300..]));
+    assert_eq!(Summary::of(&stats), Summary::of(&expected));
+
+    let count = accumulate::<((), Count)>(&measurements);
+    assert_eq!(
+        Summary::of(&count),
+        Summary {
+            count: Some(1000),
+            ..Summary::default()
+        }
+    );
+}
+
+#[test]
+fn percentiles_by_nearest_rank() {
+    let stats = accumulate::<Histogram>(&[50, -999, 10, 20, 30, 40, 60, 70, 80, 90]);
+    assert_eq!(stats.percentile(percent(0.0)), Some(-999));
+    assert_eq!(stats.percentile(percent(10.0)), Some(-999));
+    assert_eq!(stats.percentile(percent(11.0)), Some(10));
+    assert_eq!(stats.percentile(percent(50.0)), Some(40));
+    assert_eq!(stats.percentile(percent(90.0)), Some(80));
+    assert_eq!(stats.percentile(percent(99.0)), Some(90));
+    assert_eq!(stats.percentile(percent(100.0)), Some(90));
+
+    let mut other = Histogram::init(999);
+    other.merge(&stats);
+    assert_eq!(other.percentile(percent(100.0)), Some(999));
+    assert_eq!(other.percentile(percent(50.0)), Some(50));
+}
+
+#[test]
+fn percentile_ranks_are_exact() {
+    // 7 / 100 * 100 is 7.000000000000001 in floating point.
+    let measurements: Vec<i16> = (1..=100).collect();
+    let stats = accumulate::<Histogram>(&measurements);
+    for p in 1..=100 {
+        assert_eq!(stats.percentile(percent(p as f64)), Some(p), "p{p}");
+    }
+    assert_eq!(stats.percentile(percent(99.9)), Some(100));
+    // 64.4 * 250 / 100 is 161.00000000000003 in floating point.
+    let measurements: Vec<i16> = (1..=250).collect();
+    let stats = accumulate::<Histogram>(&measurements);
+    assert_eq!(stats.percentile(percent(64.4)), Some(161));
+    assert_eq!(
+        stats.percentile(Percentile::new(64_401).unwrap()),
+        Some(162)
+    );
+    assert_eq!(stats.percentile(Percentile::MAX), Some(250));
+}
+
+#[test]
+fn histograms_count_past_samples() {
+    let measurements: Vec<i16> = (0..5000).map(|i| (i * 37 % 1999 - 999) as i16).collect();
+    let all = accumulate::<Histogram>(&measurements);
+    assert!(all.samples.len() <= MAX_SAMPLES);
+    assert_eq!((all.low, all.counts.len()), (-999, 1999));
+    for split in [1, 100, 600, 4999] {
+        let mut merged = accumulate::<Histogram>(&measurements[split..]);
+        merged.merge(&accumulate(&measurements[..split]));
+        assert_eq!(merged, all);
+        assert_eq!(
+            merged.percentile(percent(50.0)),
+            all.percentile(percent(50.0))
+        );
+    }
+    let mut sorted = measurements.clone();
+    sorted.sort();
+    assert_eq!(all.percentile(percent(50.0)), Some(sorted[2499]));
+    assert_eq!(
+        Histogram::init(i16::MIN).percentile(percent(50.0)),
+        Some(-MAX_TENTHS)
+    );
+}
+
+#[test]
+fn format_percentiles() {
+    assert_eq!(Percentile::new(50_000).unwrap().to_string(), "50");
+    assert_eq!(Percentile::new(99_900).unwrap().to_string(), "99.9");
+    assert_eq!(Percentile::new(12_345).unwrap().to_string(), "12.345");
+    assert_eq!(Percentile::new(50).unwrap().to_string(), "0.05");
+    assert_eq!(Percentile::new(100_001), None);
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+
+use clap::ValueEnum;
+use rand::Rng;
+
+use crate::push_temperature;
+
+/// Rate of line defects given without one.
+const DEFAULT_RATE: f64 = 0.001;
+
+/// Byte order mark that some tools put at the start of UTF-8 files.
+pub const BOM: &[u8] = "\u{feff}".as_bytes();
+
+/// Characters that look like `;` but are not.
+const LOOKALIKE_SEPARATORS: [char; 2] = ['\u{37e}', '\u{ff1b}'];
+
+/// Ways of breaking the input of an aggregator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Defect {
+    /// `\r\n` line endings
+    Crlf,
+    /// A UTF-8 byte order mark before the first line
+    Bom,
+    /// No newline after the last line
+    NoTrailingNewline,
+    /// No `;` between the name and the temperature
+    MissingSeparator,
+    /// `-0.0` as the temperature
+    NegativeZero,
+    /// No digit before the dot, like `.5`
+    LeadingDot,
+    /// An explicit `+` sign
+    PlusSign,
+    /// Nothing before the `;`
+    EmptyName,
+    /// A Unicode character that looks like `;` inside the name
+    LookalikeSeparator,
+    /// A temperature outside -99.9..=99.9
+    OutOfRange,
+}
+
+impl Defect {
+    /// Whether the defect applies to the whole file rather than to lines.
+    fn is_file_defect(self) -> bool {
+        matches!(self, Defect::Bom | Defect::NoTrailingNewline)
+    }
+}
+
+impl fmt::Display for Defect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
+    }
+}
+
+/// Parses `defect` or `defect=rate`, the rate being the probability of each
+/// line to have the defect.
+pub fn parse_injection(injection: &str) -> Result<(Defect, f64), String> {
+    let (name, rate) = match injection.split_once('=') {
+        Some((name, rate)) => {
+            let rate = rate
+                .parse::<f64>()
+                .ok()
+                .filter(|rate| (0.0..=1.0).contains(rate))
+                .ok_or_else(|| format!("invalid rate {rate:?}, expected 0 to 1"))?;
+            (name, Some(rate))
+        }
+        None => (injection, None),
+    };
+    let defect = Defect::from_str(name, true)?;
+    match (defect.is_file_defect(), rate) {
+        (true, Some(_)) => Err(format!("{defect} happens once per file, it takes no rate")),
+        (true, None) => Ok((defect, 1.0)),
+        (false, rate) => Ok((defect, rate.unwrap_or(DEFAULT_RATE))),
+    }
+}
+
+/// Defects to mix into the generated lines.
+#[derive(Debug, Clone, Default)]
+pub struct Injector {
+    /// Line defects and their rates, at most one per line.
+    rates: Vec<(Defect, f64)>,
+    pub bom: bool,
+    pub no_trailing_newline: bool,
+}
+
+impl Injector {
+    pub fn new(injections: &[(Defect, f64)]) -> Result<Self, String> {
+        let mut injector = Injector::default();
+        for &(defect, rate) in injections {
+            match defect {
+                Defect::Bom => injector.bom = true,
+                Defect::NoTrailingNewline => injector.no_trailing_newline = true,
+                _ => injector.rates.push((defect, rate)),
+            }
+        }
+        let total: f64 = injector.rates.iter().map(|(_, rate)| rate).sum();
+        if total > 1.0 {
+            return Err(format!(
+                "the rates of line defects add up to {total}, above 1"
+            ));
+        }
+        Ok(injector)
+    }
+
+    /// Whether lines of blocks may be altered.
+    pub fn alters_lines(&self) -> bool {
+        self.bom || !self.rates.is_empty()
+    }
+
+    /// Picks the defect of a line, if any.
+    #[inline]
+    pub fn pick(&self, rng: &mut impl Rng) -> Option<Defect> {
+        let mut draw: f64 = rng.gen();
+        for &(defect, rate) in &self.rates {
+            if draw < rate {
+                return Some(defect);
+            }
+            draw -= rate;
+        }
+        None
+    }
+}
+
+/// Appends the line of a station `prefix` and `value` to `buffer`, with
+/// `defect`.
+pub fn push_defective(
+    buffer: &mut Vec<u8>,
+    prefix: &[u8],
+    value: f64,
+    defect: Defect,
+    rng: &mut impl Rng,
+) {
+    let name = &prefix[..prefix.len() - 1];
+    match defect {
+        Defect::Crlf => {
+            buffer.extend_from_slice(prefix);
+            push_temperature(buffer, value);
+            buffer.insert(buffer.len() - 1, b'\r');
+        }
+        Defect::Bom | Defect::NoTrailingNewline => {
+            unreachable!("{defect} is not a line defect")
+        }
+        Defect::MissingSeparator => {
+            buffer.extend_from_slice(name);
+            push_temperature(buffer, value);
+        }
+        Defect::NegativeZero => {
+            buffer.extend_from_slice(prefix);
+            buffer.extend_from_slice(b"-0.0\n");
+        }
+        Defect::LeadingDot => {
+            buffer.extend_from_slice(prefix);
+            let tenths = (value * 10.0).round() as i64;
+            buffer.extend_from_slice(&[b'.', b'0' + (tenths.unsigned_abs() % 10) as u8, b'\n']);
+        }
+        Defect::PlusSign => {
+            buffer.extend_from_slice(prefix);
+            buffer.push(b'+');
+            push_temperature(buffer, value.abs());
+        }
+        Defect::EmptyName => {
+            buffer.push(b';');
+            push_temperature(buffer, value);
+        }
+        Defect::LookalikeSeparator => {
+            let name = std::str::from_utf8(name).expect("station names are UTF-8");
+            let mut middle = name.len() / 2;
+            while !name.is_char_boundary(middle) {
+                middle += 1;
+            }
+            let lookalike = LOOKALIKE_SEPARATORS[rng.gen_range(0..LOOKALIKE_SEPARATORS.len())];
+            buffer.extend_from_slice(&name.as_bytes()[..middle]);
+            buffer.extend_from_slice(lookalike.encode_utf8(&mut [0; 4]).as_bytes());
+            buffer.extend_from_slice(&name.as_bytes()[middle..]);
+            buffer.push(b';');
+            push_temperature(buffer, value);
+        }
+        Defect::OutOfRange => {
+            buffer.extend_from_slice(prefix);
+            let sign = if rng.gen() { 1.0 } else { -1.0 };
+            push_temperature(buffer, sign * rng.gen_range(1000..10000) as f64 / 10.0);
+        }
+    }
+}
+
+#[test]
+fn parse_injections() {
+    assert_eq!(parse_injection("crlf=0.5"), Ok((Defect::Crlf, 0.5)));
+    assert_eq!(
+        parse_injection("out-of-range"),
+        Ok((Defect::OutOfRange, DEFAULT_RATE))
+    );
+    assert_eq!(parse_injection("bom"), Ok((Defect::Bom, 1.0)));
+    assert!(parse_injection("bom=0.5").is_err());
+    assert!(parse_injection("crlf=2").is_err());
+    assert!(parse_injection("typo").is_err());
+    assert!(Injector::new(&[(Defect::Crlf, 0.6), (Defect::PlusSign, 0.6)]).is_err());
+}
+
+#[test]
+fn defective_lines() {
+    use low_effort_1brc::{parse_record, RecordErrorKind};
+    use rand::SeedableRng;
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    let mut line = |defect| {
+        let mut buffer = Vec::new();
+        push_defective(&mut buffer, "Zürich;".as_bytes(), -12.3, defect, &mut rng);
+        String::from_utf8(buffer).unwrap()
+    };
+    assert_eq!(line(Defect::Crlf), "Zürich;-12.3\r\n");
+    assert_eq!(line(Defect::MissingSeparator), "Zürich-12.3\n");
+    assert_eq!(line(Defect::NegativeZero), "Zürich;-0.0\n");
+    assert_eq!(line(Defect::LeadingDot), "Zürich;.3\n");
+    assert_eq!(line(Defect::PlusSign), "Zürich;+12.3\n");
+    assert_eq!(line(Defect::EmptyName), ";-12.3\n");
+    let lookalike = line(Defect::LookalikeSeparator);
+    assert!(lookalike == "Zü\u{37e}rich;-12.3\n" || lookalike == "Zü\u{ff1b}rich;-12.3\n");
+    let out_of_range = line(Defect::OutOfRange);
+    assert_eq!(
+        parse_record(out_of_range.trim_end().as_bytes()),
+        Err(RecordErrorKind::TemperatureOutOfRange)
+    );
+}
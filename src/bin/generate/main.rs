@@ -0,0 +1,638 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use clap::Parser;
+use low_effort_1brc::{parse_record, Format, Results, Statistics, MAX_NAME_LEN};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+
+mod inject;
+mod selection;
+mod stations;
+mod values;
+
+use inject::{Defect, Injector};
+use selection::{Selection, Selector};
+use stations::{NamePattern, Station, STATIONS};
+use values::{OutOfRange, Sampler, Shape, ValueOptions};
+
+#[derive(Debug, Parser)]
+#[command(version, about, long_about = None, allow_missing_positional = true)]
+struct Cli {
+    /// Number of lines
+    #[arg(required_unless_present = "size", conflicts_with = "size")]
+    lines: Option<usize>,
+    /// Output file
+    out_file: String,
+    /// Approximate size of the output instead of a line count, like `1GiB` or
+    /// `500MB`, the last line ends past it
+    #[arg(long, value_parser = parse_size)]
+    size: Option<u64>,
+    /// Seed of the random generator, the same seed and line count or size
+    /// always produce the same file
+    #[arg(short, long)]
+    seed: Option<u64>,
+    /// Stations file with `name;mean[;stddev[;weight]]` rows, defaults to a
+    /// built-in list of ~400 stations
+    #[arg(long)]
+    stations: Option<PathBuf>,
+    /// Generate this many stations with unique random names instead, up to
+    /// the 10,000 the challenge allows
+    #[arg(long, conflicts_with = "stations")]
+    synthetic_stations: Option<usize>,
+    /// Shortest synthetic station name in bytes, lengths are uniform between
+    /// the shortest and the longest
+    #[arg(long, default_value_t = 1, requires = "synthetic_stations")]
+    name_min_len: usize,
+    /// Longest synthetic station name in bytes
+    #[arg(long, default_value_t = MAX_NAME_LEN, requires = "synthetic_stations")]
+    name_max_len: usize,
+    /// Share of synthetic station names made exactly as long as the longest,
+    /// from 0 to 1
+    #[arg(long, default_value_t = 0.0, requires = "synthetic_stations")]
+    long_name_share: f64,
+    /// How synthetic station names resemble each other
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = NamePattern::Random,
+        requires = "synthetic_stations"
+    )]
+    name_pattern: NamePattern,
+    /// Also write the exact results the aggregator should produce
+    #[arg(long)]
+    expected: Option<PathBuf>,
+    /// How the station of each line is picked
+    #[arg(long, value_enum, default_value_t = Selection::Uniform)]
+    selection: Selection,
+    /// Exponent of the Zipf selection, higher values favour the first
+    /// stations more
+    #[arg(long, default_value_t = 1.0)]
+    zipf_exponent: f64,
+    /// Shape of the measurements around the mean of each station
+    #[arg(long, value_enum, default_value_t = Shape::Normal)]
+    distribution: Shape,
+    /// Spread of the measurements of stations without a standard deviation of
+    /// their own
+    #[arg(long, default_value_t = 10.0)]
+    sigma: f64,
+    /// Degrees of freedom of the student-t distribution, lower values give
+    /// heavier tails
+    #[arg(long, default_value_t = 3.0)]
+    degrees_of_freedom: f64,
+    /// What to do with measurements outside -99.9..=99.9
+    #[arg(long, value_enum, default_value_t = OutOfRange::Clamp)]
+    out_of_range: OutOfRange,
+    /// Format of the expected results
+    #[arg(short, long, value_enum, default_value_t = Format::Verbose)]
+    format: Format,
+    /// Defects to mix into the output, as a comma separated list of
+    /// `defect=rate`, the rate being the share of lines with the defect.
+    /// `bom` and `no-trailing-newline` take no rate
+    #[arg(long, value_delimiter = ',', value_parser = inject::parse_injection)]
+    inject: Vec<(Defect, f64)>,
+    /// Where to list the line number of each injected defect, defaults to the
+    /// output file followed by `.manifest`
+    #[arg(long, requires = "inject")]
+    manifest: Option<PathBuf>,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    let seed = cli.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let loaded;
+    let stations: &[Station] = match (&cli.stations, cli.synthetic_stations) {
+        (Some(path), _) => {
+            loaded = stations::load(path)?;
+            &loaded
+        }
+        (None, Some(count)) => {
+            // A stream of its own keeps the names apart from the blocks' draws.
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            rng.set_stream(u64::MAX);
+            loaded = stations::synthetic(
+                count,
+                cli.name_min_len..=cli.name_max_len,
+                cli.long_name_share,
+                cli.name_pattern,
+                &mut rng,
+            )?;
+            &loaded
+        }
+        (None, None) => &STATIONS,
+    };
+    let samplers = ValueOptions {
+        shape: cli.distribution,
+        sigma: cli.sigma,
+        degrees_of_freedom: cli.degrees_of_freedom,
+        out_of_range: cli.out_of_range,
+    }
+    .samplers(stations)?;
+    let selector = Selector::new(cli.selection, stations, cli.zipf_exponent)?;
+    let injector = Injector::new(&cli.inject)?;
+    let mut buffer = BufWriter::new(File::create(&cli.out_file)?);
+
+    let generator = Generator {
+        stations,
+        samplers: &samplers,
+        selector,
+        injector,
+        seed,
+        expected: cli.expected.is_some(),
+    };
+    let length = match (cli.lines, cli.size) {
+        (Some(lines), _) => Length::Lines(lines),
+        (None, size) => Length::Bytes(size.expect("clap requires lines or size")),
+    };
+    let written = generator.write(length, &mut buffer)?;
+    buffer.flush()?;
+    eprintln!("wrote {} lines, {} bytes", written.lines, written.bytes);
+
+    if !cli.inject.is_empty() {
+        let path = match &cli.manifest {
+            Some(path) => path.clone(),
+            None => format!("{}.manifest", cli.out_file).into(),
+        };
+        let mut out = BufWriter::new(File::create(&path)?);
+        for injected in &written.defects {
+            let kind = if injected.malformed {
+                "malformed"
+            } else {
+                "valid"
+            };
+            writeln!(out, "{}\t{}\t{kind}", injected.line, injected.defect)?;
+        }
+        out.flush()?;
+        let malformed = written.defects.iter().filter(|d| d.malformed).count();
+        eprintln!(
+            "injected {} defects, {malformed} malformed lines, listed in {}",
+            written.defects.len(),
+            path.display()
+        );
+    }
+
+    if let Some(path) = &cli.expected {
+        let mut out = BufWriter::new(File::create(path)?);
+        written.results.write_to(&mut out, cli.format)?;
+        out.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Parses a number of bytes with an optional decimal (`KB`, `MB`, ...) or
+/// binary (`KiB`, `MiB`, ...) unit.
+fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid size {size:?}"))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => {
+            return Err(format!(
+                "unknown unit {unit:?}, expected B, KB, KiB, MB, MiB, GB, GiB, TB or TiB"
+            ))
+        }
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size {size:?} is too large"))
+}
+
+/// Lines generated by one task. Every block draws from its own stream of the
+/// seeded generator, so the output does not depend on the number of threads.
+const BLOCK_LINES: usize = 1 << 16;
+
+struct Generator<'a> {
+    stations: &'a [Station],
+    /// Measurements of the station at the same index.
+    samplers: &'a [Sampler],
+    selector: Selector,
+    injector: Injector,
+    seed: u64,
+    /// Whether to keep the statistics of every station.
+    expected: bool,
+}
+
+/// How much to generate.
+#[derive(Debug, Clone, Copy)]
+enum Length {
+    Lines(usize),
+    /// Lines until the output reaches this many bytes.
+    Bytes(u64),
+}
+
+/// What [`Generator::write`] wrote.
+struct Written {
+    lines: usize,
+    bytes: u64,
+    /// Results of the lines, empty unless `expected` is set. Malformed lines
+    /// are left out, like the aggregator does with `--lenient`.
+    results: Results,
+    defects: Vec<Injected>,
+}
+
+/// A defect put in a line.
+#[derive(Debug)]
+struct Injected {
+    /// Line number, starting at 1.
+    line: usize,
+    defect: Defect,
+    /// Whether the line is no longer a valid record.
+    malformed: bool,
+}
+
+/// Lines of a block and, when tracked, the statistics of each station in it.
+struct Block {
+    data: Vec<u8>,
+    lines: usize,
+    statistics: Vec<Option<Statistics>>,
+    /// Defects of the block, numbered from its first line.
+    defects: Vec<Injected>,
+    /// Valid records of lines with defects, which may not be of a station.
+    others: Vec<(Vec<u8>, Statistics)>,
+}
+
+impl Generator<'_> {
+    fn write(&self, length: Length, mut out: impl Write) -> std::io::Result<Written> {
+        let mut statistics = vec![
+            None;
+            if self.expected {
+                self.stations.len()
+            } else {
+                0
+            }
+        ];
+        let mut written = Written {
+            lines: 0,
+            bytes: 0,
+            results: Results::default(),
+            defects: Vec::new(),
+        };
+        let mut others = Vec::new();
+        let (blocks, target) = match length {
+            Length::Lines(lines) => (lines.div_ceil(BLOCK_LINES), u64::MAX),
+            Length::Bytes(bytes) => (usize::MAX, bytes),
+        };
+        let block_lines = |block: usize| match length {
+            Length::Lines(lines) => BLOCK_LINES.min(lines - block * BLOCK_LINES),
+            Length::Bytes(_) => BLOCK_LINES,
+        };
+        // Enough blocks in flight to keep every thread busy, few enough to
+        // bound memory use.
+        let batch = rayon::current_num_threads() * 4;
+        'batches: for first in (0..blocks).step_by(batch) {
+            let generated: Vec<Block> = (first..(first + batch).min(blocks))
+                .into_par_iter()
+                .map(|block| self.block(block, block_lines(block), usize::MAX))
+                .collect();
+            for (block, mut generated) in (first..).zip(generated) {
+                let left = target - written.bytes;
+                let last = generated.data.len() as u64 >= left;
+                if last {
+                    // Draw the block again up to the first line past the
+                    // target, which also keeps the statistics exact.
+                    generated = self.block(block, block_lines(block), left as usize);
+                }
+                if (last || block + 1 == blocks) && self.injector.no_trailing_newline {
+                    if let Some(b'\n') = generated.data.last() {
+                        generated.data.pop();
+                        let start = generated.data.iter().rposition(|b| *b == b'\n');
+                        let line = &generated.data[start.map_or(0, |i| i + 1)..];
+                        generated.defects.push(Injected {
+                            line: generated.lines,
+                            defect: Defect::NoTrailingNewline,
+                            malformed: parse_record(line).is_err(),
+                        });
+                    }
+                }
+                out.write_all(&generated.data)?;
+                for mut injected in generated.defects {
+                    injected.line += written.lines;
+                    written.defects.push(injected);
+                }
+                written.lines += generated.lines;
+                written.bytes += generated.data.len() as u64;
+                for (total, stats) in statistics.iter_mut().zip(generated.statistics) {
+                    merge(total, stats);
+                }
+                others.extend(generated.others);
+                if last {
+                    break 'batches;
+                }
+            }
+        }
+        written.results = self
+            .stations
+            .iter()
+            .zip(statistics)
+            .filter_map(|(station, stats)| Some((station.name().to_vec(), stats?)))
+            .chain(others)
+            .collect();
+        Ok(written)
+    }
+
+    /// Generates up to `lines` lines of `block`, stopping after the first one
+    /// ending at or past `max_bytes`.
+    fn block(&self, block: usize, lines: usize, max_bytes: usize) -> Block {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream(block as u64);
+        let mut data = Vec::with_capacity(lines.min(max_bytes) * 16);
+        let mut statistics: Vec<Option<Statistics>> = Vec::new();
+        if self.expected {
+            statistics.resize(self.stations.len(), None);
+        }
+        // Defects draw from streams of their own so that they leave the rest
+        // of the data as it would be without them.
+        let mut inject_rng = ChaCha8Rng::seed_from_u64(self.seed);
+        inject_rng.set_stream(1 << 63 | block as u64);
+        let mut defects = Vec::new();
+        let mut others = Vec::new();
+        let mut generated = 0;
+        while generated < lines && data.len() < max_bytes {
+            generated += 1;
+            let index = self.selector.pick(&mut rng);
+            let station = &self.stations[index];
+            let value = self.samplers[index].measurement(&mut rng);
+            if self.injector.alters_lines() {
+                let bom = self.injector.bom && block == 0 && generated == 1;
+                let defect = self.injector.pick(&mut inject_rng);
+                if bom || defect.is_some() {
+                    let start = data.len();
+                    if bom {
+                        data.extend_from_slice(inject::BOM);
+                    }
+                    match defect {
+                        Some(defect) => inject::push_defective(
+                            &mut data,
+                            &station.prefix,
+                            value,
+                            defect,
+                            &mut inject_rng,
+                        ),
+                        None => {
+                            data.extend_from_slice(&station.prefix);
+                            push_temperature(&mut data, value);
+                        }
+                    }
+                    let record = parse_record(&data[start..data.len() - 1]);
+                    for defect in [bom.then_some(Defect::Bom), defect].into_iter().flatten() {
+                        defects.push(Injected {
+                            line: generated,
+                            defect,
+                            malformed: record.is_err(),
+                        });
+                    }
+                    if let (true, Ok((name, tenths))) = (self.expected, record) {
+                        others.push((name.to_vec(), Statistics::new(tenths)));
+                    }
+                    continue;
+                }
+            }
+            data.extend_from_slice(&station.prefix);
+            push_temperature(&mut data, value);
+            if let Some(stats) = statistics.get_mut(index) {
+                let tenths = (value * 10.0).round() as i16;
+                match stats {
+                    Some(stats) => stats.add_measurement(tenths),
+                    None => *stats = Some(Statistics::new(tenths)),
+                }
+            }
+        }
+        Block {
+            data,
+            lines: generated,
+            statistics,
+            defects,
+            others,
+        }
+    }
+}
+
+fn merge(total: &mut Option<Statistics>, stats: Option<Statistics>) {
+    match (total, stats) {
+        (Some(total), Some(stats)) => total.merge(&stats),
+        (total @ None, stats) => *total = stats,
+        (Some(_), None) => {}
+    }
+}
+
+/// Appends `value`, already rounded to tenths, and a newline to `buffer` the
+/// way `format!("{:.1}\n")` would, but without allocating or going through
+/// float formatting.
+#[inline]
+fn push_temperature(buffer: &mut Vec<u8>, value: f64) {
+    let tenths = (value * 10.0).round() as i64;
+    if value.is_sign_negative() {
+        buffer.push(b'-');
+    }
+    let abs = tenths.unsigned_abs();
+    let mut digits = [0; 20];
+    let mut start = digits.len();
+    let mut integer = abs / 10;
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (integer % 10) as u8;
+        integer /= 10;
+        if integer == 0 {
+            break;
+        }
+    }
+    buffer.extend_from_slice(&digits[start..]);
+    buffer.extend_from_slice(&[b'.', b'0' + (abs % 10) as u8, b'\n']);
+}
+
+#[cfg(test)]
+fn default_samplers() -> Vec<Sampler> {
+    ValueOptions {
+        shape: Shape::Normal,
+        sigma: 10.0,
+        degrees_of_freedom: 3.0,
+        out_of_range: OutOfRange::Clamp,
+    }
+    .samplers(&STATIONS)
+    .unwrap()
+}
+
+#[cfg(test)]
+fn generated(length: Length, seed: u64) -> Vec<u8> {
+    let samplers = default_samplers();
+    let generator = Generator {
+        stations: &STATIONS,
+        samplers: &samplers,
+        selector: Selector::Uniform(STATIONS.len()),
+        injector: Injector::default(),
+        seed,
+        expected: false,
+    };
+    let mut out = Vec::new();
+    generator.write(length, &mut out).unwrap();
+    out
+}
+
+#[test]
+fn seeded_generation_is_deterministic() {
+    let first = generated(Length::Lines(1000), 42);
+    assert_eq!(first.iter().filter(|b| **b == b'\n').count(), 1000);
+    assert_eq!(generated(Length::Lines(1000), 42), first);
+    assert_ne!(generated(Length::Lines(1000), 43), first);
+}
+
+#[test]
+fn generation_ignores_thread_count() {
+    let lines = BLOCK_LINES * 3 + 10;
+    let expected = generated(Length::Lines(lines), 42);
+    assert_eq!(expected.iter().filter(|b| **b == b'\n').count(), lines);
+    for threads in [1, 3] {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        assert_eq!(
+            pool.install(|| generated(Length::Lines(lines), 42)),
+            expected
+        );
+    }
+}
+
+#[test]
+fn expected_matches_aggregation() {
+    let samplers = default_samplers();
+    let generator = Generator {
+        stations: &STATIONS,
+        samplers: &samplers,
+        selector: Selector::Uniform(STATIONS.len()),
+        injector: Injector::default(),
+        seed: 42,
+        expected: true,
+    };
+    for length in [Length::Lines(BLOCK_LINES + 1000), Length::Bytes(1_500_000)] {
+        let mut out = Vec::new();
+        let written = generator.write(length, &mut out).unwrap();
+        assert_eq!(written.results, low_effort_1brc::aggregate(&out));
+        assert_eq!(written.bytes, out.len() as u64);
+        assert_eq!(written.lines, out.iter().filter(|b| **b == b'\n').count());
+    }
+}
+
+#[test]
+fn injected_defects_match_lenient_aggregation() {
+    use clap::ValueEnum;
+    use low_effort_1brc::{aggregate_with, Options, Validation};
+
+    let injections: Vec<_> = Defect::value_variants()
+        .iter()
+        .map(|defect| inject::parse_injection(&format!("{defect}")).unwrap())
+        .map(|(defect, rate)| (defect, rate.min(0.05)))
+        .collect();
+    let samplers = default_samplers();
+    let generator = Generator {
+        stations: &STATIONS,
+        samplers: &samplers,
+        selector: Selector::Uniform(STATIONS.len()),
+        injector: Injector::new(&injections).unwrap(),
+        seed: 42,
+        expected: true,
+    };
+    let mut out = Vec::new();
+    let written = generator.write(Length::Lines(10_000), &mut out).unwrap();
+    assert!(out.starts_with(inject::BOM) && !out.ends_with(b"\n"));
+    for defect in Defect::value_variants() {
+        assert!(
+            written.defects.iter().any(|d| d.defect == *defect),
+            "{defect}"
+        );
+    }
+
+    let lines: Vec<&[u8]> = out.split(|b| *b == b'\n').collect();
+    for injected in &written.defects {
+        let line = lines[injected.line - 1];
+        assert_eq!(
+            parse_record(line).is_err(),
+            injected.malformed,
+            "{injected:?}"
+        );
+    }
+    let options = Options {
+        validation: Validation::Lenient,
+        ..Options::default()
+    };
+    let results = aggregate_with(&out, &options).unwrap();
+    assert_eq!(
+        results.skipped(),
+        written.defects.iter().filter(|d| d.malformed).count()
+    );
+    assert_eq!(
+        results.iter().collect::<Vec<_>>(),
+        written.results.iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn size_stops_past_the_target() {
+    let full = generated(Length::Lines(BLOCK_LINES * 2), 42);
+    for size in [0, 1, 100, 1_000_000, 1_000_001] {
+        let out = generated(Length::Bytes(size), 42);
+        assert!(out.len() as u64 >= size);
+        assert!(full.starts_with(&out));
+        let last = out[..out.len().saturating_sub(1)]
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1);
+        assert!((last as u64) < size.max(1), "{size}");
+    }
+    assert!(generated(Length::Bytes(0), 42).is_empty());
+}
+
+#[test]
+fn parse_sizes() {
+    assert_eq!(parse_size("1GiB"), Ok(1 << 30));
+    assert_eq!(parse_size("500MB"), Ok(500_000_000));
+    assert_eq!(parse_size("42"), Ok(42));
+    assert_eq!(parse_size("3 kib"), Ok(3072));
+    assert!(parse_size("1.5GB").is_err());
+    assert!(parse_size("GB").is_err());
+    assert!(parse_size("1PB").is_err());
+    assert!(parse_size("99999999TiB").is_err());
+}
+
+#[test]
+fn push_temperature_matches_format() {
+    let mut rng = ChaCha8Rng::seed_from_u64(42);
+    let samplers = default_samplers();
+    let mut buffer = Vec::new();
+    for _ in 0..100_000 {
+        let index = rng.gen_range(0..STATIONS.len());
+        let station = &STATIONS[index];
+        let measurement = samplers[index].measurement(&mut rng);
+        buffer.clear();
+        buffer.extend_from_slice(&station.prefix);
+        push_temperature(&mut buffer, measurement);
+        let name = std::str::from_utf8(station.name()).unwrap();
+        assert_eq!(buffer, format!("{name};{measurement:.1}\n").as_bytes());
+    }
+    for value in [
+        0.0, -0.0, 0.1, -0.1, 9.9, -99.9, 99.9, -100.3, 123.4, 1000.0,
+    ] {
+        buffer.clear();
+        push_temperature(&mut buffer, value);
+        assert_eq!(buffer, format!("{value:.1}\n").as_bytes());
+    }
+}
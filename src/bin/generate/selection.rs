@@ -0,0 +1,101 @@
+use clap::ValueEnum;
+use rand::Rng;
+use rand_distr::{Distribution, WeightedIndex};
+
+use crate::stations::Station;
+
+/// How the station of each line is picked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Selection {
+    /// Every station is as likely
+    #[default]
+    Uniform,
+    /// The n-th station is picked with a probability proportional to
+    /// 1 / n^exponent, so the first few dominate
+    Zipf,
+    /// Proportionally to the weights of the stations file
+    Weighted,
+}
+
+/// Picks station indices according to a [`Selection`].
+pub enum Selector {
+    Uniform(usize),
+    Weighted(WeightedIndex<f64>),
+}
+
+impl Selector {
+    pub fn new(
+        selection: Selection,
+        stations: &[Station],
+        zipf_exponent: f64,
+    ) -> Result<Self, String> {
+        Ok(match selection {
+            Selection::Uniform => Selector::Uniform(stations.len()),
+            // Weights of 1 / n^exponent are much cheaper to sample than the
+            // rejection sampling of `rand_distr::Zipf`.
+            Selection::Zipf if zipf_exponent.is_finite() && zipf_exponent >= 0.0 => {
+                let weights = (1..=stations.len()).map(|n| (n as f64).powf(-zipf_exponent));
+                Selector::Weighted(WeightedIndex::new(weights).map_err(|error| error.to_string())?)
+            }
+            Selection::Zipf => return Err(format!("invalid Zipf exponent {zipf_exponent}")),
+            Selection::Weighted => {
+                WeightedIndex::new(stations.iter().map(|s| s.weight.unwrap_or(1.0)))
+                    .map(Selector::Weighted)
+                    .map_err(|error| format!("invalid station weights: {error}"))?
+            }
+        })
+    }
+
+    #[inline]
+    pub fn pick(&self, rng: &mut impl Rng) -> usize {
+        match self {
+            Selector::Uniform(len) => rng.gen_range(0..*len),
+            Selector::Weighted(weighted) => weighted.sample(rng),
+        }
+    }
+}
+
+#[test]
+fn selections_follow_their_distribution() {
+    use rand::SeedableRng;
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    let stations = [
+        Station::new("A", 0.0),
+        Station {
+            weight: Some(3.0),
+            ..Station::new("B", 0.0)
+        },
+        Station {
+            weight: Some(0.0),
+            ..Station::new("C", 0.0)
+        },
+        Station::new("D", 0.0),
+    ];
+    let mut counts = |selection| {
+        let selector = Selector::new(selection, &stations, 2.0).unwrap();
+        let mut counts = [0; 4];
+        for _ in 0..100_000 {
+            counts[selector.pick(&mut rng)] += 1;
+        }
+        counts
+    };
+
+    assert!(counts(Selection::Uniform)
+        .iter()
+        .all(|c| (24_000..26_000).contains(c)));
+    // 1, 1/4, 1/9 and 1/16 over their sum of ~1.42.
+    let zipf = counts(Selection::Zipf);
+    assert!((69_000..71_500).contains(&zipf[0]), "{zipf:?}");
+    assert!((4_000..5_000).contains(&zipf[3]), "{zipf:?}");
+    let weighted = counts(Selection::Weighted);
+    assert_eq!(weighted[2], 0);
+    assert!((59_000..61_000).contains(&weighted[1]), "{weighted:?}");
+
+    assert!(Selector::new(Selection::Zipf, &stations, -1.0).is_err());
+    let zero = [Station {
+        weight: Some(0.0),
+        ..Station::new("A", 0.0)
+    }];
+    assert!(Selector::new(Selection::Weighted, &zero, 1.0).is_err());
+}
@@ -1,61 +1,36 @@
-use std::{
-    fs::File,
-    io::{BufWriter, Write},
-};
+use std::{collections::HashSet, error::Error, fs, ops::RangeInclusive, path::Path};
 
-use clap::Parser;
+use clap::ValueEnum;
+use low_effort_1brc::MAX_NAME_LEN;
 use once_cell::sync::Lazy;
 use rand::Rng;
-use rand_distr::{Distribution, Normal};
 
-#[derive(Debug, Parser)]
-#[command(version, about, long_about = None)]
-struct Cli {
-    /// Number of lines, min = 10000, must be a multiple of 10000
-    lines: usize,
-    /// Output file
-    out_file: String,
-}
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
-    let mut rng = rand::thread_rng();
-    let mut buffer = BufWriter::new(File::create(cli.out_file)?);
-
-    (0..cli.lines).for_each(|_| {
-        let station = STATIONS
-            .get(rng.gen_range(0..STATIONS.len()))
-            .expect("should be there");
-        let measurement = format!("{};{:.1}\n", station.name, station.measurement());
-        buffer
-            .write_all(measurement.as_bytes())
-            .expect("should write");
-    });
-
-    buffer.flush().expect("should flush");
-
-    Ok(())
-}
-
-struct Station {
-    name: String,
-    normal: Normal<f64>,
+pub struct Station {
+    /// The name followed by `;`, ready to be copied into a line.
+    pub prefix: Box<[u8]>,
+    pub mean: f64,
+    /// Spread of the measurements, `--sigma` when not given.
+    pub stddev: Option<f64>,
+    /// Relative popularity with `--selection weighted`, 1 when not given.
+    pub weight: Option<f64>,
 }
 
 impl Station {
-    fn new(name: impl ToString, avg: f64) -> Self {
+    pub fn new(name: impl ToString, mean: f64) -> Self {
         Station {
-            name: name.to_string(),
-            normal: Normal::new(avg, 10.0).unwrap(),
+            prefix: format!("{};", name.to_string()).into_bytes().into(),
+            mean,
+            stddev: None,
+            weight: None,
         }
     }
 
-    fn measurement(&self) -> f64 {
-        (self.normal.sample(&mut rand::thread_rng()) * 10.0).round() / 10.0
+    pub fn name(&self) -> &[u8] {
+        &self.prefix[..self.prefix.len() - 1]
     }
 }
 
-static STATIONS: Lazy<Vec<Station>> = Lazy::new(|| {
+pub static STATIONS: Lazy<Vec<Station>> = Lazy::new(|| {
     [
         Station::new("Abha", 18.0),
         Station::new("Abidjan", 26.0),
@@ -474,3 +449,301 @@ static STATIONS: Lazy<Vec<Station>> = Lazy::new(|| {
     .into_iter()
     .collect()
 });
+
+/// Reads a stations file of `name;mean[;stddev[;weight]]` rows, skipping
+/// blank lines and `#` comments like the upstream `weather_stations.csv`.
+pub fn load(path: &Path) -> Result<Vec<Station>, Box<dyn Error>> {
+    let data =
+        fs::read(path).map_err(|error| format!("failed to read {}: {error}", path.display()))?;
+    parse(&data).map_err(|error| format!("{}: {error}", path.display()).into())
+}
+
+fn parse(data: &[u8]) -> Result<Vec<Station>, String> {
+    let mut stations = Vec::new();
+    for (i, line) in data.split(|b| b == &b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        let station = parse_station(line).map_err(|error| format!("line {}: {error}", i + 1))?;
+        stations.push(station);
+    }
+    if stations.is_empty() {
+        return Err("no stations".to_string());
+    }
+    Ok(stations)
+}
+
+fn parse_station(line: &[u8]) -> Result<Station, String> {
+    let line = std::str::from_utf8(line).map_err(|_| "not valid UTF-8".to_string())?;
+    let fields: Vec<&str> = line.split(';').collect();
+    let (name, mean, stddev, weight) = match fields[..] {
+        [name, mean] => (name, mean, "", ""),
+        [name, mean, stddev] => (name, mean, stddev, ""),
+        [name, mean, stddev, weight] => (name, mean, stddev, weight),
+        _ => {
+            return Err(format!(
+                "expected `name;mean[;stddev[;weight]]`, got {line:?}"
+            ))
+        }
+    };
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!(
+            "station name {name:?} must be 1 to {MAX_NAME_LEN} bytes long"
+        ));
+    }
+    if name.contains(['\r', '\n']) {
+        return Err(format!("station name {name:?} contains a line break"));
+    }
+    let number = |field: &str| {
+        field
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| format!("invalid number {field:?}"))
+    };
+    // Empty optional fields fall back to their defaults.
+    let optional = |field: &str, what: &str| match field.trim() {
+        "" => Ok(None),
+        _ => match number(field)? {
+            value if value < 0.0 => Err(format!("invalid {what} {value}")),
+            value => Ok(Some(value)),
+        },
+    };
+    Ok(Station {
+        stddev: optional(stddev, "standard deviation")?,
+        weight: optional(weight, "weight")?,
+        ..Station::new(name, number(mean)?)
+    })
+}
+
+/// Characters that do not start or end a synthetic name.
+const NAME_PUNCTUATION: &[char] = &[' ', '-', '\'', '.', '(', ')'];
+
+/// Characters of synthetic names: mostly ASCII, with some two, three and four
+/// byte UTF-8 sequences.
+const NAME_CHARS: [&[char]; 4] = [
+    &[
+        'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R',
+        'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j',
+        'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '0', '1',
+        '2', '3', '4', '5', '6', '7', '8', '9', ' ', '-', '\'', '.', '(', ')',
+    ],
+    &[
+        'é', 'ü', 'ß', 'ø', 'ñ', 'ç', 'Å', 'ł', 'Ж', 'ж', 'Ω', 'ש', 'ع',
+    ],
+    &['あ', 'ン', '中', '市', '€', 'ห', 'ก', '한', '국', 'ṃ'],
+    &['😀', '🌡', '𝔸', '𐍈', '🏔'],
+];
+
+/// How synthetic names resemble each other, to find the worst case of the
+/// hashing of station names.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum NamePattern {
+    /// Unrelated names
+    #[default]
+    Random,
+    /// Names share all but their last 4 bytes
+    CommonPrefix,
+    /// Names of the same length differ only in their last byte
+    LastByte,
+    /// Names share their first 8 bytes, and are at least 9 bytes long
+    Prefix8,
+    /// Names share their first 16 bytes, and are at least 17 bytes long
+    Prefix16,
+}
+
+/// Makes `count` stations with unique random names following `pattern`, and
+/// random means. A `long_share` of the names are as long as the end of
+/// `lengths`, the length in bytes of the others is drawn uniformly from it.
+pub fn synthetic(
+    count: usize,
+    lengths: RangeInclusive<usize>,
+    long_share: f64,
+    pattern: NamePattern,
+    rng: &mut impl Rng,
+) -> Result<Vec<Station>, String> {
+    if count == 0 {
+        return Err("no stations".to_string());
+    }
+    if *lengths.start() == 0 || *lengths.end() > MAX_NAME_LEN || lengths.is_empty() {
+        return Err(format!(
+            "station names must be 1 to {MAX_NAME_LEN} bytes long, got {}..={}",
+            lengths.start(),
+            lengths.end()
+        ));
+    }
+    if !(0.0..=1.0).contains(&long_share) {
+        return Err(format!("invalid share of long names {long_share}"));
+    }
+    // The shared part of the names, ASCII so that it can be cut anywhere.
+    let letters = NAME_CHARS[0].strip_suffix(NAME_PUNCTUATION).unwrap();
+    let base: String = match pattern {
+        NamePattern::Random => String::new(),
+        _ => (0..MAX_NAME_LEN)
+            .map(|_| letters[rng.gen_range(0..letters.len())])
+            .collect(),
+    };
+    let mut names = HashSet::with_capacity(count);
+    let mut stations = Vec::with_capacity(count);
+    // Give up when short names run out rather than looping forever.
+    for _ in 0..count.saturating_mul(100) {
+        if stations.len() == count {
+            break;
+        }
+        // Only drawn when asked for, to keep the names of other seeds.
+        let len = if long_share > 0.0 && rng.gen_bool(long_share) {
+            *lengths.end()
+        } else {
+            rng.gen_range(lengths.clone())
+        };
+        let name = match pattern {
+            NamePattern::Random => synthetic_name(len, rng),
+            NamePattern::CommonPrefix => {
+                base[..len.saturating_sub(4)].to_string() + &synthetic_name(len.min(4), rng)
+            }
+            NamePattern::LastByte => {
+                let last = rng.gen_range(b'!'..=b'~');
+                if last == b';' {
+                    continue;
+                }
+                base[..len - 1].to_string() + &(last as char).to_string()
+            }
+            NamePattern::Prefix8 => base[..8].to_string() + &synthetic_name(len.max(9) - 8, rng),
+            NamePattern::Prefix16 => {
+                base[..16].to_string() + &synthetic_name(len.max(17) - 16, rng)
+            }
+        };
+        if names.insert(name.clone()) {
+            let mean = (rng.gen_range(-30.0..40.0f64) * 10.0).round() / 10.0;
+            stations.push(Station::new(name, mean));
+        }
+    }
+    if stations.len() < count {
+        return Err(format!(
+            "could not make {count} unique station names of {}..={} bytes",
+            lengths.start(),
+            lengths.end()
+        ));
+    }
+    Ok(stations)
+}
+
+/// A name of exactly `len` bytes, not starting or ending with a space.
+fn synthetic_name(len: usize, rng: &mut impl Rng) -> String {
+    let mut name = String::with_capacity(len);
+    while name.len() < len {
+        let left = len - name.len();
+        let width = match rng.gen_range(0..10) {
+            0 => 2,
+            1 => 3,
+            2 => 4,
+            _ => 1,
+        };
+        let mut chars = NAME_CHARS[width.min(left) - 1];
+        if name.is_empty() || left == 1 {
+            chars = chars.strip_suffix(NAME_PUNCTUATION).unwrap_or(chars);
+        }
+        name.push(chars[rng.gen_range(0..chars.len())]);
+    }
+    name
+}
+
+#[test]
+fn parse_stations_file() {
+    let data = "# Adapted from somewhere\nAbha;18.0\r\n\nZürich;9.3;2.5\nZed;-1.5;;20\n";
+    let stations = parse(data.as_bytes()).unwrap();
+    let prefixes: Vec<&[u8]> = stations.iter().map(|s| &s.prefix[..]).collect();
+    assert_eq!(prefixes, [&b"Abha;"[..], "Zürich;".as_bytes(), b"Zed;"]);
+    assert_eq!((stations[1].mean, stations[1].stddev), (9.3, Some(2.5)));
+    assert_eq!((stations[1].weight, stations[2].weight), (None, Some(20.0)));
+    assert_eq!(stations[2].stddev, None);
+}
+
+#[test]
+fn reject_invalid_stations() {
+    let long = format!("{};1.0", "x".repeat(MAX_NAME_LEN + 1));
+    let invalid: [&[u8]; 10] = [
+        b"Abha",
+        b";1.0",
+        long.as_bytes(),
+        b"Ab;ha;1.0;2.0;3.0",
+        b"Abha;1.0;;-1",
+        b"Abha;warm",
+        b"Abha;1.0;-1.0",
+        b"Abha;NaN",
+        b"Ab\xffha;1.0",
+        b"# only comments\n",
+    ];
+    for data in invalid {
+        assert!(parse(data).is_err(), "{:?}", String::from_utf8_lossy(data));
+    }
+    assert!(parse(format!("{};1.0", "x".repeat(MAX_NAME_LEN)).as_bytes()).is_ok());
+}
+
+#[test]
+fn synthetic_names_are_unique_and_valid() {
+    use rand::SeedableRng;
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    let stations = synthetic(10_000, 1..=MAX_NAME_LEN, 0.0, NamePattern::Random, &mut rng).unwrap();
+    let names: HashSet<&[u8]> = stations.iter().map(|s| &s.prefix[..]).collect();
+    assert_eq!(names.len(), 10_000);
+    for prefix in names {
+        let name = std::str::from_utf8(&prefix[..prefix.len() - 1]).unwrap();
+        assert!((1..=MAX_NAME_LEN).contains(&name.len()), "{name:?}");
+        assert!(
+            !name.contains([';', '\n']) && name.trim() == name,
+            "{name:?}"
+        );
+    }
+    assert!(stations.iter().any(|s| s.prefix.len() == MAX_NAME_LEN + 1));
+    assert!(stations.iter().any(|s| !s.prefix.is_ascii()));
+
+    let stations = synthetic(100, 100..=100, 0.0, NamePattern::Random, &mut rng).unwrap();
+    assert!(stations.iter().all(|s| s.prefix.len() == 101));
+    assert!(synthetic(1000, 1..=1, 0.0, NamePattern::Random, &mut rng).is_err());
+    assert!(synthetic(10, 0..=5, 0.0, NamePattern::Random, &mut rng).is_err());
+    assert!(synthetic(10, 1..=5, 1.5, NamePattern::Random, &mut rng).is_err());
+
+    let stations = synthetic(1000, 1..=MAX_NAME_LEN, 0.5, NamePattern::Random, &mut rng).unwrap();
+    let long = stations
+        .iter()
+        .filter(|s| s.prefix.len() == MAX_NAME_LEN + 1)
+        .count();
+    assert!((400..600).contains(&long), "{long}");
+}
+
+#[test]
+fn synthetic_name_patterns() {
+    use rand::SeedableRng;
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    let names = |pattern, lengths, rng: &mut rand_chacha::ChaCha8Rng| {
+        let stations = synthetic(1000, lengths, 0.0, pattern, rng).unwrap();
+        let names: Vec<Vec<u8>> = stations.iter().map(|s| s.name().to_vec()).collect();
+        assert_eq!(names.iter().collect::<HashSet<_>>().len(), names.len());
+        assert!(names
+            .iter()
+            .all(|name| std::str::from_utf8(name).is_ok() && !name.contains(&b';')));
+        names
+    };
+
+    let common = names(NamePattern::CommonPrefix, 30..=30, &mut rng);
+    assert!(common.iter().all(|name| name[..26] == common[0][..26]));
+    let last = names(NamePattern::LastByte, 20..=40, &mut rng);
+    for name in &last {
+        assert!(last
+            .iter()
+            .all(|other| other.len() != name.len()
+                || other[..name.len() - 1] == name[..name.len() - 1]));
+    }
+    for (pattern, shared) in [(NamePattern::Prefix8, 8), (NamePattern::Prefix16, 16)] {
+        let prefixed = names(pattern, 1..=MAX_NAME_LEN, &mut rng);
+        assert!(prefixed
+            .iter()
+            .all(|name| name.len() > shared && name[..shared] == prefixed[0][..shared]));
+    }
+    assert!(synthetic(1000, 5..=5, 0.0, NamePattern::LastByte, &mut rng).is_err());
+}
@@ -0,0 +1,173 @@
+use clap::ValueEnum;
+use low_effort_1brc::MAX_TENTHS;
+use rand::Rng;
+use rand_distr::{Distribution, Normal, StudentT, Uniform};
+
+use crate::stations::Station;
+
+/// Draws before [`OutOfRange::Resample`] gives up and clamps, for stations
+/// whose mean is itself out of range.
+const MAX_RESAMPLES: usize = 64;
+
+/// Shape of the measurements of every station around its mean.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Shape {
+    /// Normal with a standard deviation of sigma
+    #[default]
+    Normal,
+    /// Uniform within sigma of the mean
+    Uniform,
+    /// Student's t scaled by sigma, with heavy tails
+    StudentT,
+    /// Two normal peaks at the mean ± sigma, each with a standard deviation
+    /// of sigma / 2
+    Bimodal,
+}
+
+/// What to do with measurements outside -99.9..=99.9.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutOfRange {
+    /// Replace them with the closest bound
+    #[default]
+    Clamp,
+    /// Draw again, clamping after a few tries
+    Resample,
+}
+
+/// Settings shared by the measurements of all stations.
+#[derive(Debug, Clone, Copy)]
+pub struct ValueOptions {
+    pub shape: Shape,
+    /// Spread of stations without a standard deviation of their own.
+    pub sigma: f64,
+    /// Degrees of freedom of [`Shape::StudentT`].
+    pub degrees_of_freedom: f64,
+    pub out_of_range: OutOfRange,
+}
+
+impl ValueOptions {
+    /// Builds the sampler of each station.
+    pub fn samplers(&self, stations: &[Station]) -> Result<Vec<Sampler>, String> {
+        if !(self.sigma.is_finite() && self.sigma >= 0.0) {
+            return Err(format!("invalid sigma {}", self.sigma));
+        }
+        let t = StudentT::new(self.degrees_of_freedom)
+            .ok()
+            .filter(|_| self.degrees_of_freedom > 0.0)
+            .ok_or_else(|| format!("invalid degrees of freedom {}", self.degrees_of_freedom))?;
+        Ok(stations
+            .iter()
+            .map(|station| {
+                let mean = station.mean;
+                let sigma = station.stddev.unwrap_or(self.sigma);
+                let shape = match self.shape {
+                    Shape::Normal => Values::Normal(Normal::new(mean, sigma).unwrap()),
+                    Shape::Uniform => {
+                        Values::Uniform(Uniform::new_inclusive(mean - sigma, mean + sigma))
+                    }
+                    Shape::StudentT => Values::StudentT { mean, sigma, t },
+                    Shape::Bimodal => Values::Bimodal {
+                        low: Normal::new(mean - sigma, sigma / 2.0).unwrap(),
+                        high: Normal::new(mean + sigma, sigma / 2.0).unwrap(),
+                    },
+                };
+                Sampler {
+                    values: shape,
+                    out_of_range: self.out_of_range,
+                }
+            })
+            .collect())
+    }
+}
+
+enum Values {
+    Normal(Normal<f64>),
+    Uniform(Uniform<f64>),
+    StudentT {
+        mean: f64,
+        sigma: f64,
+        t: StudentT<f64>,
+    },
+    Bimodal {
+        low: Normal<f64>,
+        high: Normal<f64>,
+    },
+}
+
+/// Measurements of a single station.
+pub struct Sampler {
+    values: Values,
+    out_of_range: OutOfRange,
+}
+
+impl Sampler {
+    /// Draws a measurement rounded to tenths, within -99.9..=99.9.
+    pub fn measurement(&self, rng: &mut impl Rng) -> f64 {
+        let mut tenths = self.tenths(rng);
+        if self.out_of_range == OutOfRange::Resample {
+            for _ in 0..MAX_RESAMPLES {
+                if tenths.abs() <= MAX_TENTHS.into() {
+                    break;
+                }
+                tenths = self.tenths(rng);
+            }
+        }
+        let max = f64::from(MAX_TENTHS);
+        tenths.clamp(-max, max) / 10.0
+    }
+
+    fn tenths(&self, rng: &mut impl Rng) -> f64 {
+        let value = match &self.values {
+            Values::Normal(normal) => normal.sample(rng),
+            Values::Uniform(uniform) => uniform.sample(rng),
+            Values::StudentT { mean, sigma, t } => mean + sigma * t.sample(rng),
+            Values::Bimodal { low, high } => {
+                if rng.gen() {
+                    high.sample(rng)
+                } else {
+                    low.sample(rng)
+                }
+            }
+        };
+        (value * 10.0).round()
+    }
+}
+
+#[test]
+fn measurements_stay_in_range() {
+    use rand::SeedableRng;
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    let stations = [Station {
+        stddev: Some(20.0),
+        ..Station::new("Hot", 95.0)
+    }];
+    for shape in Shape::value_variants() {
+        for out_of_range in OutOfRange::value_variants() {
+            let options = ValueOptions {
+                shape: *shape,
+                sigma: 10.0,
+                degrees_of_freedom: 3.0,
+                out_of_range: *out_of_range,
+            };
+            let sampler = &options.samplers(&stations).unwrap()[0];
+            let values: Vec<f64> = (0..10_000).map(|_| sampler.measurement(&mut rng)).collect();
+            assert!(values.iter().all(|v| (-99.9..=99.9).contains(v)));
+            let at_bound = values.iter().filter(|v| **v == 99.9).count();
+            match out_of_range {
+                OutOfRange::Clamp => assert!(at_bound > 1000, "{shape:?}"),
+                OutOfRange::Resample => assert!(at_bound < 100, "{shape:?}"),
+            }
+        }
+    }
+
+    let options = ValueOptions {
+        shape: Shape::Normal,
+        sigma: 10.0,
+        degrees_of_freedom: 3.0,
+        out_of_range: OutOfRange::Resample,
+    };
+    let stations = [Station::new("Sun", -500.0)];
+    let sampler = &options.samplers(&stations).unwrap()[0];
+    assert_eq!(sampler.measurement(&mut rng), -99.9);
+}
@@ -1,123 +1,174 @@
-use std::{collections::HashMap, fs::File};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    num::NonZeroUsize,
+    path::PathBuf,
+};
 
+use anyhow::Context;
+use clap::Parser;
+use low_effort_1brc::{
+    aggregate_as, aggregate_reader_as, decompress, with_accumulator, Accumulator, Compression,
+    Executor, Format, Options, Percentile, Results, Scanner, Stat, Validation, WithAccumulator,
+};
 use memmap::Mmap;
-use rayon::prelude::*;
 
-#[derive(Debug, Clone)]
-struct Statistics {
-    min: i16,
-    max: i16,
-    sum: i64,
-    count: usize,
+#[derive(Debug, Parser)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    /// Measurements file, `-` reads from stdin
+    input: PathBuf,
+    /// Output file, defaults to stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Verbose)]
+    format: Format,
+    /// Fail on the first malformed record
+    #[arg(long, conflicts_with = "lenient")]
+    strict: bool,
+    /// Skip and count malformed records
+    #[arg(long)]
+    lenient: bool,
+    /// How records are located in the input
+    #[arg(long, value_enum, default_value_t = Scanner::Simd)]
+    scanner: Scanner,
+    /// How the work is spread over threads, for memory mapped files only;
+    /// stdin, pipes and compressed inputs are always streamed through rayon
+    #[arg(long, value_enum, default_value_t = Executor::Rayon)]
+    executor: Executor,
+    /// Worker threads, defaults to all cores
+    #[arg(short, long)]
+    threads: Option<usize>,
+    /// Bytes of input per unit of work
+    #[arg(long)]
+    chunk_size: Option<NonZeroUsize>,
+    /// Statistics of every station to compute and write, in order, instead of
+    /// those of the format
+    #[arg(long, value_enum, value_delimiter = ',')]
+    stats: Vec<Stat>,
+    /// Percentiles of every station to write after the other statistics, like
+    /// `50,90,99`
+    #[arg(long, value_delimiter = ',', value_parser = parse_percentile)]
+    percentiles: Vec<Percentile>,
 }
 
-impl Statistics {
-    #[inline]
-    fn new(measurement: i16) -> Self {
-        Statistics {
-            min: measurement,
-            max: measurement,
-            sum: measurement as i64,
-            count: 1,
+impl Cli {
+    fn options(&self) -> Options {
+        let validation = if self.strict {
+            Validation::Strict
+        } else if self.lenient {
+            Validation::Lenient
+        } else {
+            Validation::Unchecked
+        };
+        Options {
+            validation,
+            scanner: self.scanner,
+            executor: self.executor,
+            threads: self.threads,
+            chunk_size: self.chunk_size.map(NonZeroUsize::get),
         }
     }
 
-    #[inline]
-    fn average(&self) -> f64 {
-        (self.sum / self.count as i64) as f64 / 10.
+    /// Statistics to write, those of the format unless some are requested.
+    fn stats(&self) -> &[Stat] {
+        match self.stats.is_empty() {
+            true => self.format.stats(),
+            false => &self.stats,
+        }
     }
+}
 
-    #[inline]
-    fn add_measurement(&mut self, measurement: i16) {
-        self.count += 1;
-        self.min = measurement.min(self.min);
-        self.max = measurement.max(self.max);
-        self.sum += measurement as i64;
-    }
+/// Parses a percent with up to three decimals, exactly.
+fn parse_percentile(percentile: &str) -> Result<Percentile, String> {
+    let (whole, fraction) = percentile.split_once('.').unwrap_or((percentile, ""));
+    let is_number = |digits: &str| digits.len() <= 3 && digits.bytes().all(|b| b.is_ascii_digit());
+    (!whole.is_empty() && is_number(whole) && is_number(fraction))
+        .then(|| {
+            let fraction: u32 = format!("{fraction:0<3}").parse().unwrap();
+            whole.parse::<u32>().unwrap() * 1000 + fraction
+        })
+        .and_then(Percentile::new)
+        .ok_or_else(|| {
+            format!("invalid percentile {percentile:?}, expected 0 to 100 with up to 3 decimals")
+        })
+}
 
-    #[inline]
-    fn merge(&mut self, other: &Statistics) {
-        self.count += other.count;
-        self.min = other.min.min(self.min);
-        self.max = other.max.max(self.max);
-        self.sum += other.sum;
-    }
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    with_accumulator(cli.stats(), !cli.percentiles.is_empty(), Run(&cli))
 }
 
-#[inline]
-fn hacky_i16_float_parse(buf: &[u8]) -> i16 {
-    let (sign, buf) = if buf[0] == b'-' {
-        (-1i16, &buf[1..])
-    } else {
-        (1i16, buf)
-    };
+/// Aggregates and writes the results with the accumulator picked for the
+/// requested statistics.
+struct Run<'a>(&'a Cli);
 
-    let num: i16 = buf
-        .iter()
-        .filter(|b| b.is_ascii_digit())
-        .fold(0i16, |acc, b| {
-            let decimal = (b - b'0') as i16;
-            acc * 10 + decimal
-        });
-    num * sign
-}
+impl WithAccumulator for Run<'_> {
+    type Output = anyhow::Result<()>;
 
-fn main() -> anyhow::Result<()> {
-    let file = File::options().read(true).open("./measurement_data.txt")?;
-    let mmap = unsafe { Mmap::map(&file)? };
-    let result: HashMap<&[u8], Statistics> = mmap
-        .par_split(|b| b == &b'\n')
-        .filter(|buf| !buf.is_empty())
-        .fold_with(HashMap::<&[u8], Statistics>::new(), |mut map, buf| {
-            let pos = buf.iter().position(|b| b == &b';').unwrap();
-            let (station, temperature_u8) = buf.split_at(pos);
-            let temperature = hacky_i16_float_parse(&temperature_u8[1..]);
+    fn call<A: Accumulator>(self) -> Self::Output {
+        let cli = self.0;
+        let results = aggregate_input::<A>(cli)
+            .with_context(|| format!("failed to aggregate {}", cli.input.display()))?;
+        if results.skipped() > 0 {
+            eprintln!("skipped {} malformed records", results.skipped());
+        }
 
-            if let Some(stored_stats) = map.get_mut(station) {
-                stored_stats.add_measurement(temperature);
-            } else {
-                let stats = Statistics::new(temperature);
-                map.insert(station, stats);
+        let mut out: Box<dyn Write> = match &cli.output {
+            Some(path) => {
+                Box::new(BufWriter::new(File::create(path).with_context(|| {
+                    format!("failed to create {}", path.display())
+                })?))
             }
-            map
-        })
-        .reduce_with(|mut map_left, map_right| {
-            map_right.into_iter().for_each(|(station, stats)| {
-                if let Some(stored_stats) = map_left.get_mut(station) {
-                    stored_stats.merge(&stats);
-                } else {
-                    map_left.insert(station, stats);
-                }
-            });
-            map_left
-        })
-        .unwrap();
+            None => Box::new(BufWriter::new(io::stdout().lock())),
+        };
+        results.write_with(&mut out, cli.format, cli.stats(), &cli.percentiles)?;
+        out.flush()?;
 
-    let mut sorted: Vec<_> = result.into_iter().collect();
-    sorted.par_sort_by_key(|i| i.0);
+        Ok(())
+    }
+}
 
-    for (station, stats) in sorted {
-        println!(
-            "{}, count: {}, min: {:.1}, max: {:.1}, avg: {:.1}",
-            std::str::from_utf8(station)?,
-            stats.count,
-            stats.min as f64 / 10.0,
-            stats.max as f64 / 10.0,
-            stats.average(),
-        );
+/// Memory maps regular files and streams anything else, like stdin or pipes.
+///
+/// Compressed inputs are always streamed through a decoder.
+fn aggregate_input<A: Accumulator>(cli: &Cli) -> anyhow::Result<Results<A>> {
+    let options = cli.options();
+    if cli.input.as_os_str() == "-" {
+        return Ok(aggregate_reader_as(decompress(io::stdin())?, &options)?);
     }
 
-    Ok(())
-}
+    let file = File::options()
+        .read(true)
+        .open(&cli.input)
+        .with_context(|| format!("failed to open {}", cli.input.display()))?;
+    let metadata = file.metadata()?;
+    // Empty files cannot be mapped.
+    if !metadata.is_file() || metadata.len() == 0 {
+        return Ok(aggregate_reader_as(decompress(file)?, &options)?);
+    }
 
-#[test]
-fn parse_positive() {
-    assert_eq!(hacky_i16_float_parse("12.0".as_bytes()), 120);
-    assert_eq!(hacky_i16_float_parse("99.9".as_bytes()), 999);
+    let mmap = unsafe { Mmap::map(&file) }
+        .with_context(|| format!("failed to map {}", cli.input.display()))?;
+    match Compression::detect(&mmap) {
+        Compression::None => Ok(aggregate_as(&mmap, &options)?),
+        compression => Ok(aggregate_reader_as(
+            compression.decoder(&mmap[..])?,
+            &options,
+        )?),
+    }
 }
 
 #[test]
-fn parse_negative() {
-    assert_eq!(hacky_i16_float_parse("-12.0".as_bytes()), -120);
-    assert_eq!(hacky_i16_float_parse("-99.9".as_bytes()), -999);
+fn parse_percentiles() {
+    let thousandths = |p| parse_percentile(p).map(Percentile::thousandths);
+    assert_eq!(thousandths("50"), Ok(50_000));
+    assert_eq!(thousandths("64.4"), Ok(64_400));
+    assert_eq!(thousandths("99.999"), Ok(99_999));
+    assert_eq!(thousandths("0.05"), Ok(50));
+    assert_eq!(thousandths("100"), Ok(100_000));
+    for invalid in ["100.1", "1.2345", "-1", "+1", ".5", "", "1e2", "nan"] {
+        assert!(parse_percentile(invalid).is_err(), "{invalid}");
+    }
 }
@@ -0,0 +1,183 @@
+use std::io::{self, Cursor, Read};
+
+/// Compression of a measurements file, as told by its magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Lz4,
+}
+
+impl Compression {
+    const GZIP_MAGIC: &'static [u8] = &[0x1f, 0x8b];
+    const ZSTD_MAGIC: &'static [u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+    const LZ4_MAGIC: &'static [u8] = &[0x04, 0x22, 0x4d, 0x18];
+
+    /// Detects the compression from the first bytes of the input.
+    pub fn detect(start: &[u8]) -> Self {
+        if start.starts_with(Self::GZIP_MAGIC) {
+            Compression::Gzip
+        } else if start.starts_with(Self::ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if start.starts_with(Self::LZ4_MAGIC) {
+            Compression::Lz4
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Wraps `reader` into a decoder for this compression.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] when the crate was built
+    /// without the matching feature.
+    pub fn decoder<'a>(
+        self,
+        reader: impl Read + Send + 'a,
+    ) -> io::Result<Box<dyn Read + Send + 'a>> {
+        match self {
+            Compression::None => Ok(Box::new(reader)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Box::new(flate2::read::MultiGzDecoder::new(reader))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Box::new(zstd::stream::read::Decoder::new(reader)?)),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok(Box::new(Lz4Frames::new(reader))),
+            #[allow(unreachable_patterns)]
+            compression => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "input is {} compressed, rebuild with the `{}` feature",
+                    compression.name(),
+                    compression.name()
+                ),
+            )),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Compression::None => "not",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Lz4 => "lz4",
+        }
+    }
+}
+
+/// Decodes concatenated lz4 frames, which `FrameDecoder` stops after the
+/// first of.
+#[cfg(feature = "lz4")]
+struct Lz4Frames<R: Read> {
+    decoder: Option<lz4_flex::frame::FrameDecoder<io::BufReader<R>>>,
+}
+
+#[cfg(feature = "lz4")]
+impl<R: Read> Lz4Frames<R> {
+    fn new(reader: R) -> Self {
+        Lz4Frames {
+            decoder: Some(lz4_flex::frame::FrameDecoder::new(io::BufReader::new(
+                reader,
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl<R: Read> Read for Lz4Frames<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use std::io::BufRead;
+
+        while let Some(decoder) = &mut self.decoder {
+            let read = decoder.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            let mut reader = self.decoder.take().expect("checked above").into_inner();
+            if !reader.fill_buf()?.is_empty() {
+                self.decoder = Some(lz4_flex::frame::FrameDecoder::new(reader));
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// Detects the compression of `reader` and decompresses it on the fly.
+///
+/// Concatenated gzip members (as written by bgzip), zstd frames and lz4 frames
+/// are all decoded, one after the other on the reading thread.
+pub fn decompress<'a>(mut reader: impl Read + Send + 'a) -> io::Result<Box<dyn Read + Send + 'a>> {
+    let mut start = Vec::with_capacity(4);
+    (&mut reader).take(4).read_to_end(&mut start)?;
+    let compression = Compression::detect(&start);
+    compression.decoder(Cursor::new(start).chain(reader))
+}
+
+#[cfg(test)]
+fn decompressed(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    decompress(data).unwrap().read_to_end(&mut out).unwrap();
+    out
+}
+
+#[test]
+fn detect_magic() {
+    assert_eq!(Compression::detect(b"Abha;12.3\n"), Compression::None);
+    assert_eq!(Compression::detect(b""), Compression::None);
+    assert_eq!(Compression::detect(&[0x1f, 0x8b, 0x08]), Compression::Gzip);
+    assert_eq!(
+        Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd]),
+        Compression::Zstd
+    );
+    assert_eq!(
+        Compression::detect(&[0x04, 0x22, 0x4d, 0x18]),
+        Compression::Lz4
+    );
+    assert_eq!(decompressed(b"Abha;12.3\n"), b"Abha;12.3\n");
+    assert_eq!(decompressed(b"A"), b"A");
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn decompress_gzip_members() {
+    use std::io::Write;
+
+    let member = |data: &[u8]| {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    };
+    let data = [member(b"Abha;12.3\n"), member(b"Zed;1.0\n")].concat();
+    assert_eq!(decompressed(&data), b"Abha;12.3\nZed;1.0\n");
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn decompress_zstd_frames() {
+    let frame = |data: &[u8]| zstd::encode_all(data, 1).unwrap();
+    let data = [frame(b"Abha;12.3\n"), frame(b"Zed;1.0\n")].concat();
+    assert_eq!(decompressed(&data), b"Abha;12.3\nZed;1.0\n");
+}
+
+#[cfg(feature = "lz4")]
+#[test]
+fn decompress_lz4_frames() {
+    use std::io::Write;
+
+    let frame = |data: &[u8]| {
+        let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    };
+    let data = [frame(b"Abha;12.3\n"), frame(b"Zed;1.0\n")].concat();
+    assert_eq!(decompressed(&data), b"Abha;12.3\nZed;1.0\n");
+}
+
+#[cfg(not(feature = "zstd"))]
+#[test]
+fn decompress_unsupported() {
+    let error = decompress(&[0x28, 0xb5, 0x2f, 0xfd, 0x00][..])
+        .err()
+        .unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+}
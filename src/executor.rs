@@ -0,0 +1,102 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use clap::ValueEnum;
+
+use crate::{Accumulator, Options, Partial, RecordError};
+
+/// How the work is spread over threads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Executor {
+    /// Rayon work stealing over the splits of the scanner
+    #[default]
+    Rayon,
+    /// Dedicated threads, each with its own table, taking newline aligned
+    /// chunks in order
+    Threads,
+}
+
+/// Threads used when none are requested.
+pub(crate) fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// Runs `f` on a rayon pool of `threads` threads, or on the global pool.
+pub(crate) fn in_pool<T: Send>(threads: Option<usize>, f: impl FnOnce() -> T + Send) -> T {
+    match threads {
+        None => f(),
+        Some(threads) => rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("failed to spawn rayon threads")
+            .install(f),
+    }
+}
+
+/// One [`Partial`] per thread of the current rayon pool, so that memory does
+/// not grow with the number of jobs rayon splits the work into.
+pub(crate) struct WorkerPartials<A>(Vec<Mutex<Partial<A>>>);
+
+impl<A: Accumulator> WorkerPartials<A> {
+    pub(crate) fn new() -> Self {
+        WorkerPartials(
+            (0..rayon::current_num_threads())
+                .map(|_| Mutex::default())
+                .collect(),
+        )
+    }
+
+    /// The partial of the calling thread, only ever locked by it.
+    pub(crate) fn get(&self) -> MutexGuard<'_, Partial<A>> {
+        let index = rayon::current_thread_index().unwrap_or(0);
+        self.0[index].lock().expect("worker panicked")
+    }
+
+    pub(crate) fn into_partial(self) -> Partial<A> {
+        let mut partial = Partial::default();
+        for worker in self.0 {
+            partial.merge(worker.into_inner().expect("worker panicked"));
+        }
+        partial
+    }
+}
+
+/// Aggregates `chunks` of `data` with [`Executor::Threads`].
+pub(crate) fn aggregate_threads<A: Accumulator>(
+    data: &[u8],
+    chunks: &[&[u8]],
+    options: &Options,
+) -> Result<Partial<A>, RecordError> {
+    let threads = options.threads.unwrap_or_else(default_threads).max(1);
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let worker = || {
+        let mut partial = Partial::default();
+        while !failed.load(Ordering::Relaxed) {
+            let Some(chunk) = chunks.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                break;
+            };
+            if let Err(error) = partial.add_chunk(data, chunk, options) {
+                failed.store(true, Ordering::Relaxed);
+                return Err(error);
+            }
+        }
+        Ok(partial)
+    };
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(chunks.len()))
+            .map(|_| scope.spawn(worker))
+            .collect();
+        let mut partial = Partial::default();
+        for worker in workers {
+            partial.merge(worker.join().expect("worker panicked")?);
+        }
+        Ok(partial)
+    })
+}
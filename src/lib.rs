@@ -0,0 +1,421 @@
+//! Aggregation of `station;temperature` measurement files as described by the
+//! [1brc](https://github.com/gunnarmorling/1brc).
+//!
+//! ```
+//! let results = low_effort_1brc::aggregate(b"Abha;12.3\nAbha;-4.0\nZed;1.0\n");
+//! let abha = results.get(b"Abha").unwrap();
+//! assert_eq!(abha.count(), 2);
+//! assert_eq!(abha.min(), -40);
+//! assert_eq!(abha.average(), 42);
+//! ```
+
+use clap::ValueEnum;
+use executor::{aggregate_threads, default_threads, in_pool, WorkerPartials};
+use rayon::prelude::*;
+use scan::{for_each_line, split_chunks};
+
+mod accumulator;
+mod compression;
+mod executor;
+mod parse;
+mod record;
+mod results;
+mod scan;
+mod statistics;
+mod stream;
+mod table;
+
+pub use accumulator::{
+    with_accumulator, Accumulator, Count, Histogram, Max, Mean, Min, Percentile, StdDev, Sum,
+    Summary, WithAccumulator,
+};
+pub use compression::{decompress, Compression};
+pub use executor::Executor;
+pub use parse::parse_temperature;
+pub use record::{parse_record, RecordError, RecordErrorKind, MAX_NAME_LEN, MAX_TENTHS};
+pub use results::{Format, Results, Stat};
+pub use scan::Scanner;
+pub use statistics::{Statistics, Tenths};
+pub use stream::{aggregate_reader, aggregate_reader_as, StreamError, STREAM_CHUNK_SIZE};
+pub use table::StationTable;
+
+/// How malformed records are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Validation {
+    /// Trust the input to be well formed, as the challenge allows, only
+    /// failing on lines without `;`
+    #[default]
+    Unchecked,
+    /// Fail on the first malformed record
+    Strict,
+    /// Skip and count malformed records
+    Lenient,
+}
+
+/// Bytes of input handed to a worker at once by [`Scanner::Simd`] on rayon.
+pub const SCAN_CHUNK_SIZE: usize = 1 << 20;
+
+/// Knobs for [`aggregate_with`] and [`aggregate_reader`].
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub validation: Validation,
+    pub scanner: Scanner,
+    /// Only used by [`aggregate_with`], streams are always aggregated on
+    /// rayon.
+    pub executor: Executor,
+    /// Worker threads, all cores by default.
+    pub threads: Option<usize>,
+    /// Bytes of input per unit of work, rounded up to the next newline.
+    ///
+    /// Defaults to [`SCAN_CHUNK_SIZE`] on rayon, to an equal share per thread
+    /// with [`Executor::Threads`] and to [`STREAM_CHUNK_SIZE`] for streams.
+    /// [`Scanner::Split`] on rayon leaves the splitting to rayon instead.
+    pub chunk_size: Option<usize>,
+}
+
+/// Aggregates every `station;temperature` line of `data` in parallel.
+///
+/// Lines are expected to be well formed; empty lines are ignored. Panics on
+/// a line without `;`, see [`Validation::Unchecked`].
+pub fn aggregate(data: &[u8]) -> Results {
+    aggregate_with(data, &Options::default()).expect("line without a `;` separator")
+}
+
+/// Aggregates `data` like [`aggregate`], validating records as requested.
+///
+/// With [`Validation::Strict`] the error of a malformed record is returned,
+/// which is not necessarily the first one in the file when there are several.
+/// With [`Validation::Lenient`] they are counted in [`Results::skipped`].
+pub fn aggregate_with(data: &[u8], options: &Options) -> Result<Results, RecordError> {
+    aggregate_as(data, options)
+}
+
+/// Aggregates `data` like [`aggregate_with`] into accumulators of type `A`
+/// rather than [`Statistics`], so that only the statistics of `A` are
+/// computed.
+pub fn aggregate_as<A: Accumulator>(
+    data: &[u8],
+    options: &Options,
+) -> Result<Results<A>, RecordError> {
+    let partial = match options.executor {
+        Executor::Rayon => in_pool(options.threads, || aggregate_rayon(data, options))?,
+        Executor::Threads => {
+            let threads = options.threads.unwrap_or_else(default_threads).max(1);
+            let chunk_size = options
+                .chunk_size
+                .unwrap_or_else(|| data.len().div_ceil(threads));
+            aggregate_threads(data, &split_chunks(data, chunk_size), options)?
+        }
+    };
+    Ok(partial.into_results())
+}
+
+fn aggregate_rayon<A: Accumulator>(
+    data: &[u8],
+    options: &Options,
+) -> Result<Partial<A>, RecordError> {
+    let validation = options.validation;
+    let partials = WorkerPartials::new();
+    match options.scanner {
+        Scanner::Split => data
+            .par_split(|b| b == &b'\n')
+            .filter(|line| is_record(data, line, validation))
+            .try_fold_with(Partial::default(), |mut partial, line| {
+                partial.add_line(data, line, validation)?;
+                Ok(partial)
+            })
+            .try_for_each(|partial| {
+                partials.get().merge(partial?);
+                Ok(())
+            })?,
+        Scanner::Simd => split_chunks(data, options.chunk_size.unwrap_or(SCAN_CHUNK_SIZE))
+            .into_par_iter()
+            .try_for_each(|chunk| partials.get().add_chunk(data, chunk, options))?,
+    }
+    Ok(partials.into_partial())
+}
+
+#[inline]
+fn offset_of(data: &[u8], line: &[u8]) -> usize {
+    line.as_ptr() as usize - data.as_ptr() as usize
+}
+
+#[inline]
+fn is_record(data: &[u8], line: &[u8], validation: Validation) -> bool {
+    // Only the empty "line" after a trailing newline is never a record.
+    !line.is_empty() || (validation != Validation::Unchecked && offset_of(data, line) < data.len())
+}
+
+/// Statistics of a part of the input.
+#[derive(Clone)]
+struct Partial<A> {
+    table: StationTable<A>,
+    skipped: usize,
+}
+
+impl<A> Default for Partial<A> {
+    fn default() -> Self {
+        Partial {
+            table: StationTable::default(),
+            skipped: 0,
+        }
+    }
+}
+
+impl<A: Accumulator> Partial<A> {
+    /// Adds every line of `chunk`, a part of `data`, with the requested
+    /// scanner.
+    fn add_chunk(
+        &mut self,
+        data: &[u8],
+        chunk: &[u8],
+        options: &Options,
+    ) -> Result<(), RecordError> {
+        let validation = options.validation;
+        match options.scanner {
+            Scanner::Split => {
+                for line in chunk
+                    .split(|b| b == &b'\n')
+                    .filter(|line| is_record(chunk, line, validation))
+                {
+                    self.add_line(data, line, validation)?;
+                }
+                Ok(())
+            }
+            Scanner::Simd => for_each_line(chunk, |start, separator, end| {
+                let line = &chunk[start..end];
+                match validation {
+                    Validation::Unchecked if line.is_empty() => Ok(()),
+                    Validation::Unchecked => match separator {
+                        Some(separator) => {
+                            self.add_unchecked(data, line, separator - start);
+                            Ok(())
+                        }
+                        None => self.add_checked(data, line, Validation::Strict),
+                    },
+                    _ => self.add_checked(data, line, validation),
+                }
+            }),
+        }
+    }
+
+    /// Adds a `line` of `data`, validating it as requested.
+    #[inline]
+    fn add_line(
+        &mut self,
+        data: &[u8],
+        line: &[u8],
+        validation: Validation,
+    ) -> Result<(), RecordError> {
+        match validation {
+            Validation::Unchecked => match line.iter().position(|b| b == &b';') {
+                Some(separator) => {
+                    self.add_unchecked(data, line, separator);
+                    Ok(())
+                }
+                // A line that cannot be split fails like with strict validation.
+                None => self.add_checked(data, line, Validation::Strict),
+            },
+            _ => self.add_checked(data, line, validation),
+        }
+    }
+
+    /// Adds a `line` of `data` whose `;` is at `separator`.
+    #[inline]
+    fn add_unchecked(&mut self, data: &[u8], line: &[u8], separator: usize) {
+        // Parse from `data` rather than `line` so that the whole word past the
+        // temperature can be read at once.
+        let start = offset_of(data, line) + separator + 1;
+        let (temperature, _) = parse_temperature(&data[start..]);
+        self.table.add(&line[..separator], temperature);
+    }
+
+    fn add_checked(
+        &mut self,
+        data: &[u8],
+        line: &[u8],
+        validation: Validation,
+    ) -> Result<(), RecordError> {
+        match parse_record(line) {
+            Ok((station, temperature)) => self.table.add(station, temperature),
+            Err(kind) if validation == Validation::Strict => {
+                return Err(RecordError::at(data, offset_of(data, line), kind))
+            }
+            Err(_) => self.skipped += 1,
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: Partial<A>) {
+        self.table.merge(&other.table);
+        self.skipped += other.skipped;
+    }
+
+    fn into_results(self) -> Results<A> {
+        Results::from(self.table).with_skipped(self.skipped)
+    }
+}
+
+#[cfg(test)]
+const MALFORMED: &[u8] = b"Abha;12.3\nAbha;-4.0\nZed\nZed;1.0\n\nZed;12.3.4\n";
+
+#[test]
+fn strict_reports_position() {
+    for scanner in [Scanner::Split, Scanner::Simd] {
+        let options = Options {
+            validation: Validation::Strict,
+            scanner,
+            ..Options::default()
+        };
+        let error = aggregate_with(b"Abha;12.3\nAbha;-4.0\nZed;1\n", &options).unwrap_err();
+        assert_eq!(
+            error,
+            RecordError {
+                line: 3,
+                offset: 20,
+                kind: RecordErrorKind::InvalidTemperature,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "line 3 (byte offset 20): invalid temperature"
+        );
+        assert!(aggregate_with(MALFORMED, &options).is_err());
+        assert!(aggregate_with(b"Abha;12.3\n\nZed;1.0", &options).is_err());
+        assert!(aggregate_with(b"Abha;12.3\nZed;1.0", &options).is_ok());
+        assert!(aggregate_with(b"", &options).unwrap().is_empty());
+    }
+}
+
+#[test]
+fn unchecked_reports_missing_separator() {
+    for scanner in [Scanner::Split, Scanner::Simd] {
+        for executor in [Executor::Rayon, Executor::Threads] {
+            let options = Options {
+                scanner,
+                executor,
+                ..Options::default()
+            };
+            let error = aggregate_with(b"Abha;12.3\nZed\n", &options).unwrap_err();
+            assert_eq!(
+                error,
+                RecordError {
+                    line: 2,
+                    offset: 10,
+                    kind: RecordErrorKind::MissingSeparator,
+                }
+            );
+            assert!(aggregate_reader(&b"Abha;12.3\nZed"[..], &options).is_err());
+        }
+    }
+}
+
+#[test]
+fn lenient_skips_malformed() {
+    for scanner in [Scanner::Split, Scanner::Simd] {
+        let options = Options {
+            validation: Validation::Lenient,
+            scanner,
+            ..Options::default()
+        };
+        let results = aggregate_with(MALFORMED, &options).unwrap();
+        assert_eq!(results.skipped(), 3);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results.get(b"Abha").unwrap().count(), 2);
+        assert_eq!(results.get(b"Zed").unwrap().count(), 1);
+    }
+}
+
+#[test]
+fn scanners_agree() {
+    let data = b"Abha;12.3\nAbidjan;-4.0\nAbha;1.0\n\nZed;-99.9\nAbidjan;0.5\nAbha;-0.3";
+    let split = Options {
+        scanner: Scanner::Split,
+        ..Options::default()
+    };
+    let simd = Options {
+        scanner: Scanner::Simd,
+        ..Options::default()
+    };
+    let expected = aggregate_with(data, &split).unwrap();
+    assert_eq!(expected.len(), 3);
+    assert_eq!(aggregate_with(data, &simd).unwrap(), expected);
+}
+
+#[test]
+fn executors_agree() {
+    let mut data = Vec::new();
+    for i in 0..2000 {
+        data.extend_from_slice(
+            format!("{};{}.{}\n", "Station".repeat(i % 7), i % 100, i % 10).as_bytes(),
+        );
+    }
+    let expected = aggregate(&data);
+    for scanner in [Scanner::Split, Scanner::Simd] {
+        for executor in [Executor::Rayon, Executor::Threads] {
+            for (threads, chunk_size) in [
+                (None, None),
+                (Some(1), None),
+                (Some(3), Some(1)),
+                (Some(4), Some(1000)),
+            ] {
+                let options = Options {
+                    scanner,
+                    executor,
+                    threads,
+                    chunk_size,
+                    ..Options::default()
+                };
+                assert_eq!(
+                    aggregate_with(&data, &options).unwrap(),
+                    expected,
+                    "{options:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn threads_report_position() {
+    let options = Options {
+        validation: Validation::Strict,
+        executor: Executor::Threads,
+        threads: Some(2),
+        chunk_size: Some(4),
+        ..Options::default()
+    };
+    let error = aggregate_with(b"Abha;12.3\nAbha;-4.0\nZed;1\n", &options).unwrap_err();
+    assert_eq!((error.line, error.offset), (3, 20));
+}
+
+#[test]
+fn accumulators_agree() {
+    let data =
+        b"Abha;12.3\nAbidjan;-4.0\nAbha;1.0\nZed;-99.9\nAbidjan;0.5\nAbha;-0.3\n".repeat(100);
+    let options = Options {
+        chunk_size: Some(64),
+        ..Options::default()
+    };
+    let expected = aggregate_as::<(Statistics, Histogram)>(&data, &options).unwrap();
+    assert_eq!(
+        expected
+            .get(b"Abha")
+            .unwrap()
+            .1
+            .percentile(Percentile::new(50_000).unwrap()),
+        Some(10)
+    );
+    for executor in [Executor::Rayon, Executor::Threads] {
+        let options = Options {
+            executor,
+            threads: Some(3),
+            ..options.clone()
+        };
+        assert_eq!(aggregate_as(&data, &options).unwrap(), expected);
+        assert_eq!(aggregate_reader_as(&data[..], &options).unwrap(), expected);
+    }
+
+    let counts = aggregate_as::<Count>(&data, &options).unwrap();
+    assert_eq!(counts.get(b"Abha"), Some(&Count(300)));
+}
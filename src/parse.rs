@@ -0,0 +1,67 @@
+/// Parses a temperature `-?\d?\d\.\d` at the start of `buf` into tenths of a
+/// degree, also returning the length of the temperature and the newline
+/// following it.
+///
+/// Reads a whole 8 byte word at once: the dot is the first of bytes 1 to 3
+/// whose bit 4 is unset (it is set for every digit), and a leading `-` also
+/// has bit 4 unset. The digits are then aligned so that a single
+/// multiplication sums them with their weights. `buf` is copied into a padded
+/// word when shorter than 8 bytes.
+///
+/// Input that does not match the pattern gives meaningless results.
+#[inline]
+pub fn parse_temperature(buf: &[u8]) -> (i16, usize) {
+    let word = match buf.get(..8) {
+        Some(word) => u64::from_le_bytes(word.try_into().unwrap()),
+        None => {
+            let mut padded = [0; 8];
+            padded[..buf.len()].copy_from_slice(buf);
+            u64::from_le_bytes(padded)
+        }
+    };
+
+    let dot = (!word & 0x1010_1000).trailing_zeros();
+    // All ones for a negative value, zero otherwise.
+    let sign = ((!word << 59) as i64 >> 63) as u64;
+    let unsigned = word & !(sign & 0xff);
+    // Move the dot to byte 3, putting the digits in bytes 1, 2 and 4.
+    let digits = (unsigned << (28 - dot)) & 0x0f_000f_0f00;
+    let abs = (digits.wrapping_mul(0x640a_0001) >> 32) & 0x3ff;
+    let value = (abs ^ sign).wrapping_sub(sign) as i64 as i16;
+    (value, (dot as usize >> 3) + 3)
+}
+
+#[test]
+fn parse_positive() {
+    assert_eq!(parse_temperature("12.0".as_bytes()).0, 120);
+    assert_eq!(parse_temperature("99.9".as_bytes()).0, 999);
+}
+
+#[test]
+fn parse_negative() {
+    assert_eq!(parse_temperature("-12.0".as_bytes()).0, -120);
+    assert_eq!(parse_temperature("-99.9".as_bytes()).0, -999);
+}
+
+#[test]
+fn parse_every_value() {
+    for tenths in -999i16..=999 {
+        let sign = if tenths < 0 { "-" } else { "" };
+        let text = format!("{sign}{}.{}", tenths.abs() / 10, tenths.abs() % 10);
+        let len = text.len() + 1;
+        for line in [
+            format!("{text}\n"),
+            format!("{text}\nAbha;12.3\n"),
+            format!("{text}\nZürich;-99.9\n"),
+        ] {
+            assert_eq!(
+                parse_temperature(line.as_bytes()),
+                (tenths, len),
+                "{line:?}"
+            );
+        }
+        // Last line without a trailing newline.
+        assert_eq!(parse_temperature(text.as_bytes()).0, tenths, "{text}");
+    }
+    assert_eq!(parse_temperature(b"-0.0\n"), (0, 5));
+}
@@ -0,0 +1,162 @@
+use std::{error::Error, fmt};
+
+/// Longest station name allowed by the challenge rules, in bytes.
+pub const MAX_NAME_LEN: usize = 100;
+
+/// Largest measurement allowed by the challenge rules, in tenths of a degree.
+pub const MAX_TENTHS: i16 = 999;
+
+/// Why a record was rejected by [`parse_record`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordErrorKind {
+    MissingSeparator,
+    EmptyName,
+    NameTooLong,
+    InvalidTemperature,
+    TooManyFractionalDigits,
+    TemperatureOutOfRange,
+}
+
+impl fmt::Display for RecordErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            RecordErrorKind::MissingSeparator => "missing `;` separator",
+            RecordErrorKind::EmptyName => "empty station name",
+            RecordErrorKind::NameTooLong => "station name longer than 100 bytes",
+            RecordErrorKind::InvalidTemperature => "invalid temperature",
+            RecordErrorKind::TooManyFractionalDigits => {
+                "temperature has more than one fractional digit"
+            }
+            RecordErrorKind::TemperatureOutOfRange => "temperature outside -99.9..99.9",
+        };
+        f.write_str(msg)
+    }
+}
+
+/// A malformed record and where it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordError {
+    /// 1-based line number.
+    pub line: usize,
+    /// Byte offset of the start of the line.
+    pub offset: usize,
+    pub kind: RecordErrorKind,
+}
+
+impl RecordError {
+    /// Locates `offset` within `data`, counting the lines before it.
+    pub(crate) fn at(data: &[u8], offset: usize, kind: RecordErrorKind) -> Self {
+        let line = data[..offset].iter().filter(|b| **b == b'\n').count() + 1;
+        RecordError { line, offset, kind }
+    }
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {} (byte offset {}): {}",
+            self.line, self.offset, self.kind
+        )
+    }
+}
+
+impl Error for RecordError {}
+
+/// Splits and validates a single `station;temperature` line, without its
+/// trailing newline.
+///
+/// Temperatures must look like `-?\d{1,2}\.\d`.
+pub fn parse_record(line: &[u8]) -> Result<(&[u8], i16), RecordErrorKind> {
+    let pos = line
+        .iter()
+        .position(|b| b == &b';')
+        .ok_or(RecordErrorKind::MissingSeparator)?;
+    let (station, temperature) = (&line[..pos], &line[pos + 1..]);
+    if station.is_empty() {
+        return Err(RecordErrorKind::EmptyName);
+    }
+    if station.len() > MAX_NAME_LEN {
+        return Err(RecordErrorKind::NameTooLong);
+    }
+    Ok((station, parse_temperature_strict(temperature)?))
+}
+
+fn parse_temperature_strict(buf: &[u8]) -> Result<i16, RecordErrorKind> {
+    let (negative, buf) = match buf.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, buf),
+    };
+    let dot = buf
+        .iter()
+        .position(|b| b == &b'.')
+        .ok_or(RecordErrorKind::InvalidTemperature)?;
+    let (integer, fraction) = (&buf[..dot], &buf[dot + 1..]);
+    let all_digits = |digits: &[u8]| !digits.is_empty() && digits.iter().all(u8::is_ascii_digit);
+    if !all_digits(integer) || !all_digits(fraction) {
+        return Err(RecordErrorKind::InvalidTemperature);
+    }
+    if fraction.len() > 1 {
+        return Err(RecordErrorKind::TooManyFractionalDigits);
+    }
+    let leading_zeros = integer.iter().take_while(|b| **b == b'0').count();
+    let integer = &integer[leading_zeros..];
+    // Also keeps the value from overflowing.
+    if integer.len() > 3 {
+        return Err(RecordErrorKind::TemperatureOutOfRange);
+    }
+
+    let value = integer
+        .iter()
+        .chain(fraction)
+        .fold(0i16, |acc, b| acc * 10 + (b - b'0') as i16);
+    if value > MAX_TENTHS {
+        return Err(RecordErrorKind::TemperatureOutOfRange);
+    }
+    Ok(if negative { -value } else { value })
+}
+
+#[test]
+fn parse_valid_records() {
+    assert_eq!(parse_record(b"Abha;12.3"), Ok((&b"Abha"[..], 123)));
+    assert_eq!(parse_record(b"Abha;-0.1"), Ok((&b"Abha"[..], -1)));
+    assert_eq!(parse_record(b"Abha;-99.9"), Ok((&b"Abha"[..], -999)));
+    assert_eq!(parse_record(b"Abha;99.9"), Ok((&b"Abha"[..], 999)));
+    assert_eq!(parse_record(b"Abha;05.0"), Ok((&b"Abha"[..], 50)));
+    assert_eq!(parse_record(b"Abha;000.5"), Ok((&b"Abha"[..], 5)));
+    assert_eq!(
+        parse_record(b"A b;c;1.0"),
+        Err(RecordErrorKind::InvalidTemperature)
+    );
+    let long = [b'x'; MAX_NAME_LEN];
+    let line = [&long[..], b";1.0"].concat();
+    assert_eq!(parse_record(&line), Ok((&long[..], 10)));
+}
+
+#[test]
+fn parse_invalid_records() {
+    use RecordErrorKind::*;
+
+    assert_eq!(parse_record(b"Abha"), Err(MissingSeparator));
+    assert_eq!(parse_record(b""), Err(MissingSeparator));
+    assert_eq!(parse_record(b";1.0"), Err(EmptyName));
+    let line = [&[b'x'; MAX_NAME_LEN + 1][..], b";1.0"].concat();
+    assert_eq!(parse_record(&line), Err(NameTooLong));
+    for temperature in [
+        "", "-", "abc", "12", "12.", ".5", "+1.0", "12.3.4", "1a.0", "--1.0",
+    ] {
+        let line = format!("Abha;{temperature}");
+        assert_eq!(
+            parse_record(line.as_bytes()),
+            Err(InvalidTemperature),
+            "{temperature}"
+        );
+    }
+    assert_eq!(parse_record(b"Abha;12.34"), Err(TooManyFractionalDigits));
+    assert_eq!(parse_record(b"Abha;100.0"), Err(TemperatureOutOfRange));
+    assert_eq!(parse_record(b"Abha;-100.0"), Err(TemperatureOutOfRange));
+    assert_eq!(
+        parse_record(b"Abha;123456789.0"),
+        Err(TemperatureOutOfRange)
+    );
+}
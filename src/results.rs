@@ -0,0 +1,286 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+use clap::ValueEnum;
+
+use crate::{Accumulator, Percentile, Statistics, Summary, Tenths};
+
+/// How [`Results`] are written out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// One `Station, count: N, min: x, max: y, avg: z` line per station
+    #[default]
+    Verbose,
+    /// The reference `{Station=min/avg/max, ...}` single line
+    #[value(name = "1brc")]
+    OneBrc,
+}
+
+impl Format {
+    /// Statistics written when none are requested.
+    pub fn stats(self) -> &'static [Stat] {
+        match self {
+            Format::Verbose => &[Stat::Count, Stat::Min, Stat::Max, Stat::Mean],
+            Format::OneBrc => &[Stat::Min, Stat::Mean, Stat::Max],
+        }
+    }
+}
+
+/// Statistics that can be written for every station.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Stat {
+    /// Number of measurements
+    Count,
+    /// Lowest measurement
+    Min,
+    /// Highest measurement
+    Max,
+    /// Mean, rounded to tenths like the reference implementation
+    #[value(alias = "avg")]
+    Mean,
+    /// Sum of the measurements
+    Sum,
+    /// Population standard deviation, in degrees
+    Stddev,
+    /// Population variance, in squared degrees
+    Variance,
+}
+
+impl Stat {
+    fn name(self) -> &'static str {
+        match self {
+            Stat::Count => "count",
+            Stat::Min => "min",
+            Stat::Max => "max",
+            Stat::Mean => "avg",
+            Stat::Sum => "sum",
+            Stat::Stddev => "stddev",
+            Stat::Variance => "variance",
+        }
+    }
+
+    /// Writes the statistic from `summary`, `-` if it was not computed.
+    fn write(self, mut out: impl Write, summary: &Summary) -> io::Result<()> {
+        let tenths = |value: Option<i64>| value.map(|value| Tenths(value).to_string());
+        let value = match self {
+            Stat::Count => summary.count.map(|count| count.to_string()),
+            Stat::Min => tenths(summary.min.map(i64::from)),
+            Stat::Max => tenths(summary.max.map(i64::from)),
+            Stat::Mean => tenths(summary.mean),
+            Stat::Sum => tenths(summary.sum),
+            Stat::Stddev => summary.stddev().map(|stddev| format!("{stddev:.1}")),
+            Stat::Variance => summary.variance.map(|variance| format!("{variance:.2}")),
+        };
+        out.write_all(value.as_deref().unwrap_or("-").as_bytes())
+    }
+}
+
+/// Per station statistics, sorted by station name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Results<A = Statistics> {
+    stations: Vec<(Vec<u8>, A)>,
+    skipped: usize,
+}
+
+impl<A> Default for Results<A> {
+    fn default() -> Self {
+        Results {
+            stations: Vec::new(),
+            skipped: 0,
+        }
+    }
+}
+
+impl<A: Accumulator> Results<A> {
+    pub(crate) fn with_skipped(mut self, skipped: usize) -> Self {
+        self.skipped = skipped;
+        self
+    }
+
+    /// Number of malformed records left out by lenient validation.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Number of distinct stations.
+    pub fn len(&self) -> usize {
+        self.stations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stations.is_empty()
+    }
+
+    pub fn get(&self, station: &[u8]) -> Option<&A> {
+        self.stations
+            .binary_search_by(|(name, _)| name.as_slice().cmp(station))
+            .ok()
+            .map(|i| &self.stations[i].1)
+    }
+
+    /// Iterates stations in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &A)> {
+        self.stations
+            .iter()
+            .map(|(name, stats)| (name.as_slice(), stats))
+    }
+
+    pub fn write_to(&self, out: impl Write, format: Format) -> io::Result<()> {
+        self.write_with(out, format, &[], &[])
+    }
+
+    /// Writes `stats` of every station, or those of the format when empty,
+    /// followed by the given percentiles. Statistics the accumulator does not
+    /// compute are written as `-`.
+    pub fn write_with(
+        &self,
+        mut out: impl Write,
+        format: Format,
+        stats: &[Stat],
+        percentiles: &[Percentile],
+    ) -> io::Result<()> {
+        let stats = if stats.is_empty() {
+            format.stats()
+        } else {
+            stats
+        };
+        let percentile = |summary: &Summary, p: Percentile| match summary.percentile(p) {
+            Some(value) => Tenths(value.into()).to_string(),
+            None => "-".to_string(),
+        };
+        match format {
+            Format::Verbose => {
+                for (station, accumulator) in self.iter() {
+                    let summary = Summary::of(accumulator);
+                    out.write_all(station)?;
+                    for stat in stats {
+                        write!(out, ", {}: ", stat.name())?;
+                        stat.write(&mut out, &summary)?;
+                    }
+                    for p in percentiles {
+                        write!(out, ", p{p}: {}", percentile(&summary, *p))?;
+                    }
+                    writeln!(out)?;
+                }
+            }
+            Format::OneBrc => {
+                write!(out, "{{")?;
+                for (i, (station, accumulator)) in self.iter().enumerate() {
+                    let summary = Summary::of(accumulator);
+                    if i > 0 {
+                        write!(out, ", ")?;
+                    }
+                    out.write_all(station)?;
+                    for (i, stat) in stats.iter().enumerate() {
+                        out.write_all(if i == 0 { b"=" } else { b"/" })?;
+                        stat.write(&mut out, &summary)?;
+                    }
+                    for p in percentiles {
+                        write!(out, "/{}", percentile(&summary, *p))?;
+                    }
+                }
+                writeln!(out, "}}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Collects stations in any order, merging repeated names.
+impl<A: Accumulator> FromIterator<(Vec<u8>, A)> for Results<A> {
+    fn from_iter<T: IntoIterator<Item = (Vec<u8>, A)>>(iter: T) -> Self {
+        let mut map: HashMap<Vec<u8>, A> = HashMap::new();
+        for (station, stats) in iter {
+            if let Some(stored_stats) = map.get_mut(&station) {
+                stored_stats.merge(&stats);
+            } else {
+                map.insert(station, stats);
+            }
+        }
+        let mut stations: Vec<_> = map.into_iter().collect();
+        stations.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Results {
+            stations,
+            skipped: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+fn written(results: &Results, format: Format) -> String {
+    let mut out = Vec::new();
+    results.write_to(&mut out, format).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn write_formats() {
+    let mut abha = Statistics::new(123);
+    abha.add_measurement(-40);
+    let results: Results = [
+        (b"Zed".to_vec(), Statistics::new(10)),
+        (b"Abha".to_vec(), abha),
+    ]
+    .into_iter()
+    .collect();
+
+    assert_eq!(
+        written(&results, Format::Verbose),
+        "Abha, count: 2, min: -4.0, max: 12.3, avg: 4.2\n\
+         Zed, count: 1, min: 1.0, max: 1.0, avg: 1.0\n"
+    );
+    assert_eq!(
+        written(&results, Format::OneBrc),
+        "{Abha=-4.0/4.2/12.3, Zed=1.0/1.0/1.0}\n"
+    );
+    assert_eq!(written(&Results::default(), Format::OneBrc), "{}\n");
+}
+
+#[test]
+fn write_selected_statistics() {
+    use crate::{Histogram, Min};
+
+    let mut abha = <(Statistics, Histogram)>::init(123);
+    abha.add(-40);
+    abha.add(7);
+    let results: Results<_> = [
+        (b"Abha".to_vec(), abha),
+        (b"Zed".to_vec(), <(Statistics, Histogram)>::init(10)),
+    ]
+    .into_iter()
+    .collect();
+    let written = |format| {
+        let mut out = Vec::new();
+        results
+            .write_with(
+                &mut out,
+                format,
+                &[Stat::Count, Stat::Mean, Stat::Stddev, Stat::Variance],
+                &[
+                    Percentile::new(50_000).unwrap(),
+                    Percentile::new(99_900).unwrap(),
+                ],
+            )
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    assert_eq!(
+        written(Format::Verbose),
+        "Abha, count: 3, avg: 3.0, stddev: 6.9, variance: 46.93, p50: 0.7, p99.9: 12.3\n\
+         Zed, count: 1, avg: 1.0, stddev: 0.0, variance: 0.00, p50: 1.0, p99.9: 1.0\n"
+    );
+    assert_eq!(
+        written(Format::OneBrc),
+        "{Abha=3/3.0/6.9/46.93/0.7/12.3, Zed=1/1.0/0.0/0.00/1.0/1.0}\n"
+    );
+
+    let results: Results<Min> = [(b"Abha".to_vec(), Min(-40))].into_iter().collect();
+    let mut out = Vec::new();
+    results
+        .write_with(&mut out, Format::OneBrc, &[], &[Percentile::MAX])
+        .unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "{Abha=-4.0/-/-/-}\n");
+}
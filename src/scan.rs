@@ -0,0 +1,256 @@
+use clap::ValueEnum;
+
+/// How records are located in the input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Scanner {
+    /// Split lines with rayon, then search each line for its `;`
+    Split,
+    /// Find every `;` and newline in a single SIMD pass over chunks
+    #[default]
+    Simd,
+}
+
+const BLOCK: usize = 64;
+
+/// Calls `f` with the start, first `;` and end of every line of `data`, all
+/// relative to `data`. The end is the position of the newline, or the length
+/// of `data` for a last line without one.
+///
+/// Uses AVX2 or SSE2 when available to compare 64 bytes at a time against both
+/// delimiters, and walks the resulting bit masks.
+#[inline]
+pub(crate) fn for_each_line<E>(
+    data: &[u8],
+    f: impl FnMut(usize, Option<usize>, usize) -> Result<(), E>,
+) -> Result<(), E> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 was just detected.
+            return unsafe { for_each_line_avx2(data, f) };
+        }
+        // SAFETY: SSE2 is part of the x86_64 baseline.
+        unsafe { for_each_line_sse2(data, f) }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    for_each_line_with(data, masks_portable, f)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn for_each_line_avx2<E>(
+    data: &[u8],
+    f: impl FnMut(usize, Option<usize>, usize) -> Result<(), E>,
+) -> Result<(), E> {
+    for_each_line_with(data, |block| masks_avx2(block), f)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+unsafe fn for_each_line_sse2<E>(
+    data: &[u8],
+    f: impl FnMut(usize, Option<usize>, usize) -> Result<(), E>,
+) -> Result<(), E> {
+    for_each_line_with(data, |block| masks_sse2(block), f)
+}
+
+#[inline(always)]
+fn for_each_line_with<E>(
+    data: &[u8],
+    masks: impl Fn(&[u8; BLOCK]) -> (u64, u64),
+    mut f: impl FnMut(usize, Option<usize>, usize) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut start = 0;
+    let mut separator = None;
+    let mut base = 0;
+    while base < data.len() {
+        let (separators, newlines) = match data.get(base..base + BLOCK) {
+            Some(block) => masks(block.try_into().unwrap()),
+            None => {
+                let mut padded = [0; BLOCK];
+                padded[..data.len() - base].copy_from_slice(&data[base..]);
+                masks(&padded)
+            }
+        };
+
+        let mut delimiters = separators | newlines;
+        while delimiters != 0 {
+            let bit = delimiters.trailing_zeros();
+            delimiters &= delimiters - 1;
+            let pos = base + bit as usize;
+            if newlines & (1 << bit) != 0 {
+                f(start, separator, pos)?;
+                start = pos + 1;
+                separator = None;
+            } else if separator.is_none() {
+                separator = Some(pos);
+            }
+        }
+        base += BLOCK;
+    }
+    if start < data.len() {
+        f(start, separator, data.len())?;
+    }
+    Ok(())
+}
+
+/// Bit masks of the `;` and newline bytes of `block`.
+#[cfg_attr(target_arch = "x86_64", allow(dead_code))]
+#[inline(always)]
+fn masks_portable(block: &[u8; BLOCK]) -> (u64, u64) {
+    block
+        .iter()
+        .enumerate()
+        .fold((0, 0), |(separators, newlines), (i, b)| {
+            (
+                separators | ((*b == b';') as u64) << i,
+                newlines | ((*b == b'\n') as u64) << i,
+            )
+        })
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+fn masks_sse2(block: &[u8; BLOCK]) -> (u64, u64) {
+    use std::arch::x86_64::*;
+
+    let separator = _mm_set1_epi8(b';' as i8);
+    let newline = _mm_set1_epi8(b'\n' as i8);
+    let (mut separators, mut newlines) = (0, 0);
+    for i in 0..BLOCK / 16 {
+        // SAFETY: the 16 bytes read are within `block`.
+        let bytes = unsafe { _mm_loadu_si128(block.as_ptr().add(i * 16).cast()) };
+        let found = _mm_movemask_epi8(_mm_cmpeq_epi8(bytes, separator)) as u16 as u64;
+        separators |= found << (i * 16);
+        let found = _mm_movemask_epi8(_mm_cmpeq_epi8(bytes, newline)) as u16 as u64;
+        newlines |= found << (i * 16);
+    }
+    (separators, newlines)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn masks_avx2(block: &[u8; BLOCK]) -> (u64, u64) {
+    use std::arch::x86_64::*;
+
+    let separator = _mm256_set1_epi8(b';' as i8);
+    let newline = _mm256_set1_epi8(b'\n' as i8);
+    let (mut separators, mut newlines) = (0, 0);
+    for i in 0..BLOCK / 32 {
+        // SAFETY: the 32 bytes read are within `block`.
+        let bytes = unsafe { _mm256_loadu_si256(block.as_ptr().add(i * 32).cast()) };
+        let found = _mm256_movemask_epi8(_mm256_cmpeq_epi8(bytes, separator)) as u32 as u64;
+        separators |= found << (i * 32);
+        let found = _mm256_movemask_epi8(_mm256_cmpeq_epi8(bytes, newline)) as u32 as u64;
+        newlines |= found << (i * 32);
+    }
+    (separators, newlines)
+}
+
+/// Cuts `data` into chunks of about `chunk_size` bytes ending right after a
+/// newline, except for the last one.
+pub(crate) fn split_chunks(data: &[u8], chunk_size: usize) -> Vec<&[u8]> {
+    let mut chunks = Vec::with_capacity(data.len() / chunk_size.max(1) + 1);
+    let mut rest = data;
+    while !rest.is_empty() {
+        let end = match rest.get(chunk_size.max(1)..) {
+            Some(tail) => tail
+                .iter()
+                .position(|b| b == &b'\n')
+                .map_or(rest.len(), |pos| chunk_size.max(1) + pos + 1),
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+#[cfg(test)]
+fn lines_with(
+    data: &[u8],
+    masks: impl Fn(&[u8; BLOCK]) -> (u64, u64),
+) -> Vec<(usize, Option<usize>, usize)> {
+    let mut lines = Vec::new();
+    for_each_line_with::<()>(data, masks, |start, separator, end| {
+        lines.push((start, separator, end));
+        Ok(())
+    })
+    .unwrap();
+    lines
+}
+
+#[cfg(test)]
+fn lines_naive(data: &[u8]) -> Vec<(usize, Option<usize>, usize)> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for line in data.split(|b| b == &b'\n') {
+        let end = start + line.len();
+        if start < data.len() {
+            let separator = line.iter().position(|b| b == &b';').map(|pos| start + pos);
+            lines.push((start, separator, end));
+        }
+        start = end + 1;
+    }
+    lines
+}
+
+#[cfg(test)]
+fn scanner_inputs() -> Vec<Vec<u8>> {
+    let mut inputs = vec![
+        b"".to_vec(),
+        b"\n".to_vec(),
+        b"Abha;12.3".to_vec(),
+        b"Abha;12.3\n\n;\nno separator\nA;b;c\n".to_vec(),
+    ];
+    let mut long = Vec::new();
+    for i in 0..500 {
+        long.extend_from_slice(
+            format!("{};{}.{}\n", "Station".repeat(i % 17), i % 100, i % 10).as_bytes(),
+        );
+    }
+    for len in [63, 64, 65, 127, 128, 129, long.len() - 1] {
+        inputs.push(long[..len].to_vec());
+    }
+    inputs.push(long);
+    inputs
+}
+
+#[test]
+fn portable_scanner() {
+    for input in scanner_inputs() {
+        assert_eq!(lines_with(&input, masks_portable), lines_naive(&input));
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn simd_scanners() {
+    for input in scanner_inputs() {
+        let expected = lines_naive(&input);
+        assert_eq!(
+            lines_with(&input, |block| unsafe { masks_sse2(block) }),
+            expected
+        );
+        if is_x86_feature_detected!("avx2") {
+            assert_eq!(
+                lines_with(&input, |block| unsafe { masks_avx2(block) }),
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn chunks_end_with_newlines() {
+    for input in scanner_inputs() {
+        for chunk_size in [0, 1, 10, 64, 1000] {
+            let chunks = split_chunks(&input, chunk_size);
+            assert_eq!(chunks.concat(), input);
+            for chunk in &chunks[..chunks.len().saturating_sub(1)] {
+                assert_eq!(chunk.last(), Some(&b'\n'));
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+
+/// Running min/max/sum/sum of squares/count of the measurements of a single
+/// station.
+///
+/// All values are in tenths of a degree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statistics {
+    min: i16,
+    max: i16,
+    sum: i64,
+    sum_squares: i64,
+    count: usize,
+}
+
+impl Statistics {
+    #[inline]
+    pub const fn new(measurement: i16) -> Self {
+        Statistics {
+            min: measurement,
+            max: measurement,
+            sum: measurement as i64,
+            sum_squares: measurement as i64 * measurement as i64,
+            count: 1,
+        }
+    }
+
+    #[inline]
+    pub fn min(&self) -> i16 {
+        self.min
+    }
+
+    #[inline]
+    pub fn max(&self) -> i16 {
+        self.max
+    }
+
+    #[inline]
+    pub fn sum(&self) -> i64 {
+        self.sum
+    }
+
+    /// Sum of the squared measurements, in hundredths of a squared degree.
+    #[inline]
+    pub fn sum_squares(&self) -> i64 {
+        self.sum_squares
+    }
+
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Mean in tenths of a degree, rounded half toward positive infinity like
+    /// the reference implementation.
+    #[inline]
+    pub fn average(&self) -> i64 {
+        mean(self.sum, self.count)
+    }
+
+    /// Population variance in squared degrees, computed exactly from the
+    /// integer sums before the final division.
+    pub fn variance(&self) -> f64 {
+        variance(self.sum, self.sum_squares, self.count)
+    }
+
+    /// Population standard deviation in degrees.
+    pub fn stddev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    #[inline]
+    pub fn add_measurement(&mut self, measurement: i16) {
+        self.count += 1;
+        self.min = measurement.min(self.min);
+        self.max = measurement.max(self.max);
+        self.sum += measurement as i64;
+        self.sum_squares += measurement as i64 * measurement as i64;
+    }
+
+    /// Adds the measurements of `other`.
+    #[inline]
+    pub fn merge(&mut self, other: &Statistics) {
+        self.count += other.count;
+        self.min = other.min.min(self.min);
+        self.max = other.max.max(self.max);
+        self.sum += other.sum;
+        self.sum_squares += other.sum_squares;
+    }
+}
+
+/// Mean of `count` measurements adding up to `sum`, rounded half toward
+/// positive infinity.
+#[inline]
+pub(crate) fn mean(sum: i64, count: usize) -> i64 {
+    let count = count as i64;
+    (2 * sum + count).div_euclid(2 * count)
+}
+
+/// Population variance in squared degrees of `count` measurements in tenths.
+pub(crate) fn variance(sum: i64, sum_squares: i64, count: usize) -> f64 {
+    let count = count as i128;
+    let sum = sum as i128;
+    let deviations = count * sum_squares as i128 - sum * sum;
+    deviations as f64 / (count * count * 100) as f64
+}
+
+/// Formats a value in tenths of a degree with exactly one decimal.
+pub struct Tenths(pub i64);
+
+impl fmt::Display for Tenths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        write!(f, "{}{}.{}", sign, abs / 10, abs % 10)
+    }
+}
+
+#[cfg(test)]
+fn stats_of(measurements: &[i16]) -> Statistics {
+    let mut stats = Statistics::new(measurements[0]);
+    measurements[1..]
+        .iter()
+        .for_each(|m| stats.add_measurement(*m));
+    stats
+}
+
+#[test]
+fn average_rounds_half_up() {
+    assert_eq!(stats_of(&[1, 2]).average(), 2);
+    assert_eq!(stats_of(&[14, 15]).average(), 15);
+    assert_eq!(stats_of(&[0, 0, 1, 1]).average(), 1);
+    assert_eq!(stats_of(&[10, 10, 11]).average(), 10);
+    assert_eq!(stats_of(&[10, 11, 11]).average(), 11);
+}
+
+#[test]
+fn average_negative() {
+    assert_eq!(stats_of(&[-1, -2]).average(), -1);
+    assert_eq!(stats_of(&[-14, -15]).average(), -14);
+    assert_eq!(stats_of(&[-10, -11, -11]).average(), -11);
+    assert_eq!(stats_of(&[-10, -10, -11]).average(), -10);
+    assert_eq!(stats_of(&[-999, 999, -1]).average(), 0);
+    assert_eq!(stats_of(&[-999, -999, -998]).average(), -999);
+}
+
+#[test]
+fn average_single_sample() {
+    for m in [-999, -10, -1, 0, 1, 10, 999] {
+        let stats = stats_of(&[m]);
+        assert_eq!(stats.average(), m as i64);
+        assert_eq!(stats.min, m);
+        assert_eq!(stats.max, m);
+    }
+}
+
+#[test]
+fn average_matches_reference() {
+    // Math.round(sum / count) as done by the reference implementation.
+    let reference = |sum: i64, count: i64| ((sum as f64 / count as f64) + 0.5).floor() as i64;
+    for count in 1..=20 {
+        for sum in -999 * count..=999 * count {
+            let stats = Statistics {
+                min: 0,
+                max: 0,
+                sum,
+                sum_squares: 0,
+                count: count as usize,
+            };
+            assert_eq!(stats.average(), reference(sum, count), "{sum} / {count}");
+        }
+    }
+}
+
+#[test]
+fn format_tenths() {
+    assert_eq!(Tenths(0).to_string(), "0.0");
+    assert_eq!(Tenths(5).to_string(), "0.5");
+    assert_eq!(Tenths(-5).to_string(), "-0.5");
+    assert_eq!(Tenths(-10).to_string(), "-1.0");
+    assert_eq!(Tenths(999).to_string(), "99.9");
+    assert_eq!(Tenths(-999).to_string(), "-99.9");
+    assert_eq!(Tenths(stats_of(&[-1, 0]).average()).to_string(), "0.0");
+}
+
+#[test]
+fn variance_is_exact_and_associative() {
+    assert_eq!(stats_of(&[5]).variance(), 0.0);
+    assert_eq!(stats_of(&[-999, 999]).variance(), 9980.01);
+    assert_eq!(stats_of(&[10, 20, 30, 40]).stddev(), 1.25f64.sqrt());
+    // Naive floating point sums lose the small spread around a large mean.
+    let close = stats_of(&[999, 998, 999, 998]);
+    assert_eq!(close.variance(), 0.0025);
+
+    let measurements: Vec<i16> = (0..1000).map(|i| (i * 37 % 1999 - 999) as i16).collect();
+    let all = stats_of(&measurements);
+    let mut merged = stats_of(&measurements[..300]);
+    merged.merge(&stats_of(&measurements[300..]));
+    assert_eq!(merged, all);
+    let mean = measurements.iter().map(|m| *m as f64).sum::<f64>() / 1000.0;
+    let reference = measurements
+        .iter()
+        .map(|m| (*m as f64 - mean).powi(2))
+        .sum::<f64>()
+        / 1000.0
+        / 100.0;
+    assert!((all.variance() - reference).abs() < 1e-9);
+}
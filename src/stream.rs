@@ -0,0 +1,231 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read},
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    executor::{in_pool, WorkerPartials},
+    Accumulator, Options, RecordError, Results,
+};
+
+/// Bytes read at once by [`aggregate_reader`].
+pub const STREAM_CHUNK_SIZE: usize = 8 << 20;
+
+/// Failure while aggregating a stream.
+#[derive(Debug)]
+pub enum StreamError {
+    Io(io::Error),
+    Record(RecordError),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Io(error) => write!(f, "read failed: {error}"),
+            StreamError::Record(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for StreamError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StreamError::Io(error) => Some(error),
+            StreamError::Record(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for StreamError {
+    fn from(error: io::Error) -> Self {
+        StreamError::Io(error)
+    }
+}
+
+impl From<RecordError> for StreamError {
+    fn from(error: RecordError) -> Self {
+        StreamError::Record(error)
+    }
+}
+
+/// Aggregates measurements read from `reader`, for inputs that cannot be
+/// memory mapped such as pipes.
+///
+/// The input is read in chunks of [`STREAM_CHUNK_SIZE`] bytes, or
+/// [`Options::chunk_size`], cut at the last newline, which are aggregated in
+/// parallel on rayon while the next ones are read, whatever the
+/// [`Options::executor`].
+pub fn aggregate_reader(
+    reader: impl Read + Send,
+    options: &Options,
+) -> Result<Results, StreamError> {
+    aggregate_reader_as(reader, options)
+}
+
+/// Aggregates a stream like [`aggregate_reader`] into accumulators of type
+/// `A`, see [`crate::aggregate_as`].
+pub fn aggregate_reader_as<A: Accumulator>(
+    reader: impl Read + Send,
+    options: &Options,
+) -> Result<Results<A>, StreamError> {
+    let chunk_size = options.chunk_size.unwrap_or(STREAM_CHUNK_SIZE).max(1);
+    in_pool(options.threads, || {
+        aggregate_chunks(reader, options, chunk_size)
+    })
+}
+
+fn aggregate_chunks<A: Accumulator>(
+    reader: impl Read + Send,
+    options: &Options,
+    chunk_size: usize,
+) -> Result<Results<A>, StreamError> {
+    let partials = WorkerPartials::new();
+    Chunks::new(reader, chunk_size)
+        .par_bridge()
+        .try_for_each(|chunk| {
+            let chunk = chunk?;
+            partials
+                .get()
+                .add_chunk(&chunk.data, &chunk.data, options)
+                .map_err(|mut error| {
+                    error.line += chunk.line;
+                    error.offset += chunk.offset;
+                    StreamError::Record(error)
+                })
+        })?;
+    Ok(partials.into_partial().into_results())
+}
+
+/// Whole lines read from a stream.
+struct Chunk {
+    data: Vec<u8>,
+    /// Byte offset of the chunk in the stream.
+    offset: usize,
+    /// Number of lines before the chunk.
+    line: usize,
+}
+
+struct Chunks<R> {
+    reader: R,
+    chunk_size: usize,
+    /// Start of a line that did not fit in the previous chunk.
+    carry: Vec<u8>,
+    offset: usize,
+    line: usize,
+    done: bool,
+}
+
+impl<R: Read> Chunks<R> {
+    fn new(reader: R, chunk_size: usize) -> Self {
+        Chunks {
+            reader,
+            chunk_size,
+            carry: Vec::new(),
+            offset: 0,
+            line: 0,
+            done: false,
+        }
+    }
+
+    fn read_chunk(&mut self) -> io::Result<Option<Chunk>> {
+        let mut data = std::mem::take(&mut self.carry);
+        let end = loop {
+            let searched = data.len();
+            let read = (&mut self.reader)
+                .take(self.chunk_size as u64)
+                .read_to_end(&mut data)?;
+            if read == 0 {
+                self.done = true;
+                break data.len();
+            }
+            if let Some(pos) = data[searched..].iter().rposition(|b| b == &b'\n') {
+                break searched + pos + 1;
+            }
+        };
+        if data.is_empty() {
+            return Ok(None);
+        }
+
+        self.carry = data.split_off(end);
+        let chunk = Chunk {
+            offset: self.offset,
+            line: self.line,
+            data,
+        };
+        self.offset += chunk.data.len();
+        self.line += chunk.data.iter().filter(|b| **b == b'\n').count();
+        Ok(Some(chunk))
+    }
+}
+
+impl<R: Read> Iterator for Chunks<R> {
+    type Item = io::Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        self.read_chunk().transpose()
+    }
+}
+
+#[cfg(test)]
+const MEASUREMENTS: &[u8] = b"Abha;12.3\nAbidjan;-4.0\nAbha;1.0\nZed;-99.9\nAbidjan;0.5\nAbha;-0.3";
+
+#[test]
+fn chunks_keep_whole_lines() {
+    for chunk_size in [1, 2, 3, 7, 10, 64, 1024] {
+        let chunks: Vec<_> = Chunks::new(MEASUREMENTS, chunk_size)
+            .map(Result::unwrap)
+            .collect();
+        let joined: Vec<u8> = chunks.iter().flat_map(|c| c.data.clone()).collect();
+        assert_eq!(joined, MEASUREMENTS);
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert_eq!(chunk.data.last(), Some(&b'\n'));
+            assert_eq!(
+                &MEASUREMENTS[chunk.offset..][..chunk.data.len()],
+                chunk.data
+            );
+        }
+    }
+}
+
+#[test]
+fn stream_matches_mmap() {
+    let expected = crate::aggregate(MEASUREMENTS);
+    for chunk_size in [1, 2, 3, 7, 10, 64, 1024] {
+        let results = aggregate_chunks(MEASUREMENTS, &Options::default(), chunk_size).unwrap();
+        assert_eq!(results, expected, "chunk size {chunk_size}");
+    }
+    let options = Options {
+        chunk_size: Some(0),
+        ..Options::default()
+    };
+    assert_eq!(aggregate_reader(MEASUREMENTS, &options).unwrap(), expected);
+    let empty: Results = aggregate_chunks(&b""[..], &Options::default(), 8).unwrap();
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn stream_reports_position() {
+    let options = Options {
+        validation: crate::Validation::Strict,
+        ..Options::default()
+    };
+    let data = b"Abha;12.3\nAbidjan;-4.0\nAbha;1.0\nZed\nAbidjan;0.5\n";
+    for chunk_size in [1, 4, 16, 1024] {
+        match aggregate_chunks::<crate::Statistics>(&data[..], &options, chunk_size) {
+            Err(StreamError::Record(error)) => {
+                assert_eq!(
+                    (error.line, error.offset),
+                    (4, 32),
+                    "chunk size {chunk_size}"
+                )
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
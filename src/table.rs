@@ -0,0 +1,270 @@
+use crate::{Accumulator, Results, Statistics};
+
+/// Bytes of a name kept next to its hash so most lookups never leave the slot.
+const INLINE: usize = 16;
+
+/// Slots allocated on the first insertion, enough for the ~400 stations of
+/// the reference data to stay under half load.
+const INITIAL_CAPACITY: usize = 1024;
+
+/// Open addressing map from station name to an [`Accumulator`], specialised
+/// for the challenge: at most 10k distinct names of at most 100 bytes.
+///
+/// Names are hashed from their first and last 8 bytes and length only, and the
+/// hash is stored so that probing and merging tables never hash a name again.
+/// The first 16 bytes of every name are stored inline, the whole name is
+/// copied into a shared arena on insertion. Accumulators are kept apart from
+/// the slots in insertion order, so growing never moves them.
+#[derive(Debug, Clone)]
+pub struct StationTable<A = Statistics> {
+    slots: Vec<Slot>,
+    names: Vec<u8>,
+    stats: Vec<A>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    /// Never zero for an occupied slot.
+    hash: u32,
+    len: u32,
+    inline: [u8; INLINE],
+    /// Offset of the name in `StationTable::names`.
+    name: u32,
+    /// Index of the accumulator in `StationTable::stats`.
+    stats: u32,
+}
+
+impl Slot {
+    const EMPTY: Slot = Slot {
+        hash: 0,
+        len: 0,
+        inline: [0; INLINE],
+        name: 0,
+        stats: 0,
+    };
+}
+
+/// A name prepared for lookups.
+struct Key<'a> {
+    name: &'a [u8],
+    inline: [u8; INLINE],
+    hash: u32,
+}
+
+impl<'a> Key<'a> {
+    #[inline]
+    fn new(name: &'a [u8]) -> Self {
+        let mut inline = [0; INLINE];
+        let n = name.len().min(INLINE);
+        inline[..n].copy_from_slice(&name[..n]);
+
+        let prefix = u64::from_le_bytes(inline[..8].try_into().unwrap());
+        let suffix = match name.len() {
+            0..=8 => 0,
+            len => u64::from_le_bytes(name[len - 8..].try_into().unwrap()),
+        };
+        let mixed = (prefix ^ suffix.rotate_left(29) ^ name.len() as u64)
+            .wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        Key {
+            name,
+            inline,
+            hash: (mixed >> 32) as u32 | 1,
+        }
+    }
+}
+
+impl StationTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<A> Default for StationTable<A> {
+    fn default() -> Self {
+        StationTable {
+            slots: Vec::new(),
+            names: Vec::new(),
+            stats: Vec::new(),
+        }
+    }
+}
+
+impl<A: Accumulator> StationTable<A> {
+    /// Number of distinct stations.
+    pub fn len(&self) -> usize {
+        self.stats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stats.is_empty()
+    }
+
+    /// Records a measurement of `station`.
+    #[inline]
+    pub fn add(&mut self, station: &[u8], measurement: i16) {
+        let key = Key::new(station);
+        match self.find(&key) {
+            Ok(index) => self.stats[self.slots[index].stats as usize].add(measurement),
+            Err(index) => self.insert(index, &key, A::init(measurement)),
+        }
+    }
+
+    pub fn get(&self, station: &[u8]) -> Option<&A> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        self.find(&Key::new(station))
+            .ok()
+            .map(|index| &self.stats[self.slots[index].stats as usize])
+    }
+
+    /// Adds every station of `other`, reusing its stored hashes.
+    pub fn merge(&mut self, other: &StationTable<A>) {
+        for slot in other.occupied() {
+            let key = Key {
+                name: other.name(slot),
+                inline: slot.inline,
+                hash: slot.hash,
+            };
+            self.merge_key(&key, &other.stats[slot.stats as usize]);
+        }
+    }
+
+    /// Iterates stations in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &A)> {
+        self.occupied()
+            .map(|slot| (self.name(slot), &self.stats[slot.stats as usize]))
+    }
+
+    fn occupied(&self) -> impl Iterator<Item = &Slot> {
+        self.slots.iter().filter(|slot| slot.hash != 0)
+    }
+
+    #[inline]
+    fn name(&self, slot: &Slot) -> &[u8] {
+        &self.names[slot.name as usize..][..slot.len as usize]
+    }
+
+    fn merge_key(&mut self, key: &Key, stats: &A) {
+        match self.find(key) {
+            Ok(index) => self.stats[self.slots[index].stats as usize].merge(stats),
+            Err(index) => self.insert(index, key, stats.clone()),
+        }
+    }
+
+    /// Index of the slot holding `key`, or of the empty slot where it belongs.
+    #[inline]
+    fn find(&self, key: &Key) -> Result<usize, usize> {
+        if self.slots.is_empty() {
+            return Err(0);
+        }
+        let mask = self.slots.len() - 1;
+        let mut index = key.hash as usize & mask;
+        loop {
+            let slot = &self.slots[index];
+            if slot.hash == 0 {
+                return Err(index);
+            }
+            if slot.hash == key.hash
+                && slot.len as usize == key.name.len()
+                && slot.inline == key.inline
+                && (key.name.len() <= INLINE || self.name(slot)[INLINE..] == key.name[INLINE..])
+            {
+                return Ok(index);
+            }
+            index = (index + 1) & mask;
+        }
+    }
+
+    #[cold]
+    fn insert(&mut self, index: usize, key: &Key, stats: A) {
+        let index = if (self.len() + 1) * 2 > self.slots.len() {
+            self.grow();
+            self.find(key).unwrap_err()
+        } else {
+            index
+        };
+
+        let name = self.names.len() as u32;
+        self.names.extend_from_slice(key.name);
+        self.slots[index] = Slot {
+            hash: key.hash,
+            len: key.name.len() as u32,
+            inline: key.inline,
+            name,
+            stats: self.stats.len() as u32,
+        };
+        self.stats.push(stats);
+    }
+
+    fn grow(&mut self) {
+        let capacity = (self.slots.len() * 2).max(INITIAL_CAPACITY);
+        let old = std::mem::replace(&mut self.slots, vec![Slot::EMPTY; capacity]);
+        let mask = capacity - 1;
+        for slot in old.into_iter().filter(|slot| slot.hash != 0) {
+            let mut index = slot.hash as usize & mask;
+            while self.slots[index].hash != 0 {
+                index = (index + 1) & mask;
+            }
+            self.slots[index] = slot;
+        }
+    }
+}
+
+impl<A: Accumulator> From<StationTable<A>> for Results<A> {
+    fn from(table: StationTable<A>) -> Self {
+        table
+            .iter()
+            .map(|(station, stats)| (station.to_vec(), stats.clone()))
+            .collect()
+    }
+}
+
+#[test]
+fn table_matches_hash_map() {
+    use std::collections::HashMap;
+
+    let names: Vec<Vec<u8>> = (0..3000u32)
+        .map(|i| match i % 3 {
+            0 => format!("{i}").into_bytes(),
+            1 => format!("{i:0>16}").into_bytes(),
+            _ => format!("{i:x<100}").into_bytes(),
+        })
+        .collect();
+    let mut table = StationTable::new();
+    let mut map: HashMap<&[u8], Statistics> = HashMap::new();
+    for (i, name) in names.iter().cycle().take(20_000).enumerate() {
+        let measurement = (i % 1999) as i16 - 999;
+        table.add(name, measurement);
+        map.entry(name)
+            .and_modify(|stats| stats.add_measurement(measurement))
+            .or_insert_with(|| Statistics::new(measurement));
+    }
+
+    assert_eq!(table.len(), map.len());
+    for (name, stats) in &map {
+        assert_eq!(table.get(name), Some(stats));
+    }
+    assert_eq!(table.get(b"missing"), None);
+    assert_eq!(table.get(b""), None);
+}
+
+#[test]
+fn table_merge() {
+    let mut left = StationTable::new();
+    left.add(b"Abha", 10);
+    left.add(b"Hamburg with a long name", 20);
+    let mut right = StationTable::new();
+    right.add(b"Hamburg with a long name", -20);
+    right.add(b"Zed", 5);
+    left.merge(&right);
+    left.merge(&StationTable::new());
+
+    assert_eq!(left.len(), 3);
+    let hamburg = left.get(b"Hamburg with a long name").unwrap();
+    assert_eq!(
+        (hamburg.count(), hamburg.min(), hamburg.max()),
+        (2, -20, 20)
+    );
+    assert_eq!(left.get(b"Zed"), Some(&Statistics::new(5)));
+}